@@ -1,10 +1,14 @@
 use character_frequency::*;
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use std::collections::HashMap;
 use std::fs;
+use std::sync::mpsc;
+use std::thread;
 
 fn character_frequency_benchmark(c: &mut Criterion) {
     let filename = "benches/bench_text.txt";
-    let text = fs::read_to_string(filename).expect(&format!("File not found: {}", filename));
+    let text =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("File not found: {}", filename));
     c.bench_function("sequential", |b| {
         b.iter(|| sequential_character_frequencies(black_box(&text)))
     });
@@ -13,5 +17,94 @@ fn character_frequency_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, character_frequency_benchmark);
+// Pairwise merge on a new thread per addition, the way the partial maps were reduced before
+// the tree was removed. The crate no longer has this code, it is kept here to compare against.
+fn tree_merge(maps: Vec<HashMap<char, usize>>) -> HashMap<char, usize> {
+    let (tx, rx) = mpsc::channel();
+    let mut waiting_num = maps.len();
+    for map in maps {
+        tx.send(map).unwrap();
+    }
+    let mut received = Vec::with_capacity(2);
+    while waiting_num > 0 {
+        received.push(rx.recv().unwrap());
+        waiting_num -= 1;
+        if received.len() >= 2 {
+            let (a, b) = (received.pop().unwrap(), received.pop().unwrap());
+            let tx = tx.clone();
+            thread::spawn(move || tx.send(add_frequencies(a, b)).unwrap());
+            waiting_num += 1;
+        }
+    }
+    received.pop().unwrap()
+}
+
+// The frequencies of the text split in the given amount of chunks.
+fn chunk_frequencies(text: &str, chunks: usize) -> Vec<HashMap<char, usize>> {
+    let char_count = text.chars().count();
+    let chunk_size = char_count / chunks + 1;
+    (0..chunks)
+        .map(|i| {
+            let from = (i * chunk_size).min(char_count);
+            let to = ((i + 1) * chunk_size).min(char_count);
+            character_frequencies_range(text, from..to, CaseSense::Sensitive)
+        })
+        .collect()
+}
+
+// The removed tree against merge_all, which folds the maps into the largest one on the calling
+// thread like the counting functions do for every amount of threads.
+fn merge_benchmark(c: &mut Criterion) {
+    let filename = "benches/bench_text.txt";
+    let text =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("File not found: {}", filename));
+    for count in [2, 3, 16] {
+        let chunks = chunk_frequencies(&text, count);
+        assert_eq!(tree_merge(chunks.clone()), merge_all(chunks.clone()));
+        c.bench_function(&format!("merge_tree_{}_chunks", count), |b| {
+            b.iter_batched(|| chunks.clone(), tree_merge, BatchSize::SmallInput)
+        });
+        c.bench_function(&format!("merge_all_{}_chunks", count), |b| {
+            b.iter_batched(|| chunks.clone(), merge_all, BatchSize::SmallInput)
+        });
+    }
+}
+
+fn add_frequencies_benchmark(c: &mut Criterion) {
+    let large: HashMap<char, usize> = ('\u{4e00}'..'\u{9fff}').map(|ch| (ch, 1)).collect();
+    let small = character_frequencies("Hello, World!");
+    c.bench_function("add_small_into_large", |b| {
+        b.iter_batched(
+            || (small.clone(), large.clone()),
+            |(small, large)| add_frequencies(small, large),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+// The whole count through the crate's dispatch, including the merge, for each amount of threads.
+fn thread_sweep_benchmark(c: &mut Criterion) {
+    let filename = "benches/bench_text.txt";
+    let text =
+        fs::read_to_string(filename).unwrap_or_else(|_| panic!("File not found: {}", filename));
+    let mut group = c.benchmark_group("threads");
+    for threads in [1, 2, 3, 4, 8, 16] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| character_frequencies_with_n_threads(black_box(&text), threads))
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    character_frequency_benchmark,
+    merge_benchmark,
+    add_frequencies_benchmark,
+    thread_sweep_benchmark
+);
 criterion_main!(benches);
@@ -3,33 +3,710 @@
 //!
 //! Counts the character frequencies in a text over multiple threads.
 //!
+//! # no_std
+//! Without the default `std` feature the crate is `no_std` and only needs `alloc`.
+//! The threaded functions are not available, the sequential ones return a
+//! `BTreeMap` instead of a `HashMap`.
+//!
+//! # Single thread
+//! With the `single-thread` feature, or when building for a WebAssembly target without
+//! atomics like `wasm32-unknown-unknown`, no threads are spawned. The functions stay the
+//! same but every chunk of the text is counted one after another on the calling thread.
+//!
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
-use std::cmp::max;
-use std::collections::HashMap;
-use std::sync::mpsc::Sender;
-use std::sync::{mpsc, Arc};
-use std::thread;
+use alloc::collections::BTreeMap;
+#[cfg(feature = "testutil")]
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use core::fmt;
+use core::ops::{BitOr, BitOrAssign};
+use core::str::FromStr;
+
+#[cfg(feature = "std")]
+use std::{
+    borrow::Cow,
+    cmp::{max, min},
+    collections::{HashMap, HashSet},
+    fs,
+    hash::{BuildHasher, Hash},
+    io::{self, Read},
+    ops::{Deref, Range, RangeInclusive},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
+};
+
+#[cfg(feature = "tokio")]
+pub use bytes::Bytes;
+#[cfg(feature = "caseless")]
+use caseless::Caseless;
+#[cfg(feature = "encoding_rs")]
+pub use encoding_rs::Encoding;
+#[cfg(feature = "tokio")]
+use futures_core::Stream;
+#[cfg(feature = "regex")]
+pub use regex::Regex;
+#[cfg(feature = "unicode-normalization")]
+use unicode_normalization::UnicodeNormalization;
+#[cfg(feature = "unicode-properties")]
+pub use unicode_properties::GeneralCategory;
+#[cfg(feature = "unicode-properties")]
+use unicode_properties::UnicodeGeneralCategory;
+#[cfg(feature = "unicode-script")]
+pub use unicode_script::Script;
+#[cfg(feature = "unicode-script")]
+use unicode_script::UnicodeScript;
+#[cfg(feature = "unicode-segmentation")]
+use unicode_segmentation::UnicodeSegmentation;
 
 /// CaseSense enables counting characters in a Case Sensitive way.
 /// * InsensitiveASCIIOnly - ignores case, but only for ASCII characters,
-/// 'A' and 'a' are counted as the same but Greek letter 'Σ' is
-/// counted as different from it's lowercase version 'σ' because it's not ASCII.
-/// All ascii characters get converted to lowercase before counting.
-/// InsensitiveASCIIOnly is the default.
+///   'A' and 'a' are counted as the same but Greek letter 'Σ' is
+///   counted as different from it's lowercase version 'σ' because it's not ASCII.
+///   All ascii characters get converted to lowercase before counting.
+///   InsensitiveASCIIOnly is the default.
 /// * Insensitive - ignores case based on Unicode Derived Core
-/// Property Lowercase, so 'A'=='a' and also 'Σ'=='σ'.
-/// This does not deal with situations where case depends on position within
-/// a word. It changes all UTF8 characters to lowercase one at a time.
-/// Some UTF8 characters have a lowercase version that is a string, if that
-/// happens the code will panic!() if Insensitive is the CaseSense.
+///   Property Lowercase, so 'A'=='a' and also 'Σ'=='σ'.
+///   This does not deal with situations where case depends on position within
+///   a word. It changes all UTF8 characters to lowercase one at a time.
+///   Some UTF8 characters have a lowercase version that is a string, if that
+///   happens the code will panic!() if Insensitive is the CaseSense.
 /// * Sensitive - Each character is counted separately.
-/// 'A' != 'a' and 'Σ'!='σ'. No characters are changed to lowercase.
+///   'A' != 'a' and 'Σ'!='σ'. No characters are changed to lowercase.
+/// * InsensitiveLocale - same as Insensitive but applying the lowercase rules
+///   of the given Locale first, see Locale for the supported ones.
+/// * InsensitiveUppercase - same as Insensitive but converting to uppercase,
+///   so 'a' and 'σ' are counted as 'A' and 'Σ'. Some UTF8 characters have an
+///   uppercase version that is a string, like 'ß', if that happens the code
+///   will panic!() if InsensitiveUppercase is the CaseSense.
+/// * InsensitiveASCIIOnlyUppercase - same as InsensitiveASCIIOnly but converting
+///   ASCII characters to uppercase.
+/// * CaseFold - ignores case with the full Unicode case folding, so final sigma 'ς'
+///   is counted as 'σ' too. Characters folding to several characters, like 'ß' to "ss",
+///   are counted as each of them. The functions that map every character to a single
+///   one, like character_positions(), panic!() on those instead.
+///   Requires the `caseless` feature.
 /// * See also <https://doc.rust-lang.org/std/string/struct.String.html#method.to_ascii_lowercase>
-#[derive(Clone, Copy)]
+///
+/// CaseSense is displayed and parsed, ignoring case, with the names "sensitive", "insensitive",
+/// "ascii", "turkish", "azerbaijani", "uppercase", "ascii-uppercase" and, with the `caseless`
+/// feature, "casefold".
+///
+/// CaseSense is non exhaustive, as features can add variants like CaseFold.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let case: CaseSense = "Insensitive".parse().unwrap();
+/// # assert_eq!(case, CaseSense::Insensitive);
+/// assert_eq!(case.to_string(), "insensitive");
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
 pub enum CaseSense {
     Insensitive,
     InsensitiveASCIIOnly,
     Sensitive,
+    InsensitiveLocale(Locale),
+    InsensitiveUppercase,
+    InsensitiveASCIIOnlyUppercase,
+    #[cfg(feature = "caseless")]
+    CaseFold,
+}
+
+impl fmt::Display for CaseSense {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CaseSense::Insensitive => "insensitive",
+            CaseSense::InsensitiveASCIIOnly => "ascii",
+            CaseSense::Sensitive => "sensitive",
+            CaseSense::InsensitiveLocale(Locale::Turkish) => "turkish",
+            CaseSense::InsensitiveLocale(Locale::Azerbaijani) => "azerbaijani",
+            CaseSense::InsensitiveUppercase => "uppercase",
+            CaseSense::InsensitiveASCIIOnlyUppercase => "ascii-uppercase",
+            #[cfg(feature = "caseless")]
+            CaseSense::CaseFold => "casefold",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for CaseSense {
+    type Err = ParseCaseSenseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "insensitive" => Ok(CaseSense::Insensitive),
+            "ascii" => Ok(CaseSense::InsensitiveASCIIOnly),
+            "sensitive" => Ok(CaseSense::Sensitive),
+            "turkish" => Ok(CaseSense::InsensitiveLocale(Locale::Turkish)),
+            "azerbaijani" => Ok(CaseSense::InsensitiveLocale(Locale::Azerbaijani)),
+            "uppercase" => Ok(CaseSense::InsensitiveUppercase),
+            "ascii-uppercase" => Ok(CaseSense::InsensitiveASCIIOnlyUppercase),
+            #[cfg(feature = "caseless")]
+            "casefold" => Ok(CaseSense::CaseFold),
+            _ => Err(ParseCaseSenseError(s.into())),
+        }
+    }
+}
+
+// The names CaseSense is parsed from, in the order they are listed by ParseCaseSenseError.
+const CASE_SENSE_NAMES: &[&str] = &[
+    "sensitive",
+    "insensitive",
+    "ascii",
+    "turkish",
+    "azerbaijani",
+    "uppercase",
+    "ascii-uppercase",
+    #[cfg(feature = "caseless")]
+    "casefold",
+];
+
+/// ParseCaseSenseError is returned when parsing a string that is not the name of a CaseSense.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseCaseSenseError(String);
+
+impl fmt::Display for ParseCaseSenseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown case sense {:?}, expected ", self.0)?;
+        let (last, names) = CASE_SENSE_NAMES.split_last().unwrap();
+        write!(f, "{} or {}", names.join(", "), last)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseCaseSenseError {}
+
+/// Locale selects language specific lowercase rules for CaseSense::InsensitiveLocale.
+/// Characters without a language specific rule are lowercased as in CaseSense::Insensitive.
+/// * Turkish - dotted and dotless I are different letters, so 'I' is lowercased
+///   to dotless 'ı' and 'İ' to dotted 'i'.
+/// * Azerbaijani - same rules as Turkish.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Locale {
+    Turkish,
+    Azerbaijani,
+}
+
+/// Tiebreak selects how characters with the same frequency are ordered by ranked_frequencies()
+/// and Counter::count_ranked().
+/// * ByChar - in ascending order of char. ByChar is the default, and the order used by
+///   FrequencyMap::iter_ranked() and bottom_n().
+/// * ByFirstOccurrence - in the order they first appear in the text.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Tiebreak {
+    #[default]
+    ByChar,
+    ByFirstOccurrence,
+}
+
+/// Expansion selects what happens to a character whose lowercase or uppercase version is
+/// a multicharacter String under an insensitive CaseSense, like 'İ' with Insensitive or
+/// 'ß' with InsensitiveUppercase, instead of panicking or returning a FreqError.
+/// * Expand - each character of the String is counted, so 'ß' counts two 'S'.
+///   Expand is the default.
+/// * Skip - the character is not counted.
+/// * Replace - the given character is counted instead.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Expansion {
+    #[default]
+    Expand,
+    Skip,
+    Replace(char),
+}
+
+/// SkipSet selects categories of characters to leave out of the count.
+/// Flags can be combined with `|` to skip characters from several categories.
+/// * SKIP_WHITESPACE - skips characters for which char::is_whitespace() is true.
+/// * SKIP_CONTROL - skips characters for which char::is_control() is true.
+/// * SKIP_PUNCTUATION - skips characters for which char::is_ascii_punctuation() is true.
+/// * SKIP_UNPRINTABLE - skips the characters that can't be printed to a terminal: the control
+///   characters, the line and paragraph separators and invisible format characters like
+///   U+200B ZERO WIDTH SPACE or the bidirectional marks. See also escape_unprintable().
+/// * NONE - skips no characters.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let skip = SkipSet::SKIP_WHITESPACE | SkipSet::SKIP_PUNCTUATION;
+/// # assert!(skip.contains(SkipSet::SKIP_WHITESPACE));
+/// # assert!(!skip.contains(SkipSet::SKIP_CONTROL));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SkipSet(u8);
+
+impl SkipSet {
+    pub const NONE: SkipSet = SkipSet(0);
+    pub const SKIP_WHITESPACE: SkipSet = SkipSet(1);
+    pub const SKIP_CONTROL: SkipSet = SkipSet(1 << 1);
+    pub const SKIP_PUNCTUATION: SkipSet = SkipSet(1 << 2);
+    pub const SKIP_UNPRINTABLE: SkipSet = SkipSet(1 << 3);
+
+    /// Returns true if all the flags of other are also set in self.
+    pub fn contains(self, other: SkipSet) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if the character belongs to any of the skipped categories.
+    pub fn skips(self, ch: char) -> bool {
+        (self.contains(SkipSet::SKIP_WHITESPACE) && ch.is_whitespace())
+            || (self.contains(SkipSet::SKIP_CONTROL) && ch.is_control())
+            || (self.contains(SkipSet::SKIP_PUNCTUATION) && ch.is_ascii_punctuation())
+            || (self.contains(SkipSet::SKIP_UNPRINTABLE) && is_unprintable(ch))
+    }
+}
+
+fn is_unprintable(ch: char) -> bool {
+    ch.is_control()
+        || matches!(
+            ch,
+            '\u{ad}'
+                | '\u{200b}'..='\u{200f}'
+                | '\u{2028}'..='\u{202e}'
+                | '\u{2060}'..='\u{2064}'
+                | '\u{2066}'..='\u{2069}'
+                | '\u{feff}'
+        )
+}
+
+/// Returns the character as a string that is safe to print to a terminal, escaping the
+/// characters skipped by SkipSet::SKIP_UNPRINTABLE and the whitespace other than ' '.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # assert_eq!(escape_unprintable('a'), "a");
+/// # assert_eq!(escape_unprintable('\t'), "\\t");
+/// assert_eq!(escape_unprintable('\u{7}'), "\\u{7}");
+/// ```
+#[cfg(feature = "std")]
+pub fn escape_unprintable(ch: char) -> String {
+    match ch {
+        '\t' | '\n' | '\r' => ch.escape_default().to_string(),
+        ' ' => ch.to_string(),
+        _ if ch.is_whitespace() || is_unprintable(ch) => ch.escape_unicode().to_string(),
+        _ => ch.to_string(),
+    }
+}
+
+impl BitOr for SkipSet {
+    type Output = SkipSet;
+
+    fn bitor(self, other: SkipSet) -> SkipSet {
+        SkipSet(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for SkipSet {
+    fn bitor_assign(&mut self, other: SkipSet) {
+        self.0 |= other.0;
+    }
+}
+
+/// FreqError is returned by the try_ functions in the cases where the other functions panic.
+/// * MultiCharacterLowercase - the lowercase version of the character is a multicharacter
+///   String, so it can't be counted as a single character.
+/// * MultiCharacterUppercase - the uppercase version of the character is a multicharacter
+///   String, so it can't be counted as a single character.
+/// * MultiCharacterCaseFold - the case folding of the character is a multicharacter String,
+///   returned by the functions that can't count it as several characters.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FreqError {
+    MultiCharacterLowercase(char),
+    MultiCharacterUppercase(char),
+    MultiCharacterCaseFold(char),
+}
+
+impl fmt::Display for FreqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FreqError::MultiCharacterLowercase(ch) => write!(
+                f,
+                "Unicode character {:?} {} when converted to lowercase is a multicharacter String not a character",
+                ch, ch
+            ),
+            FreqError::MultiCharacterUppercase(ch) => write!(
+                f,
+                "Unicode character {:?} {} when converted to uppercase is a multicharacter String not a character",
+                ch, ch
+            ),
+            FreqError::MultiCharacterCaseFold(ch) => write!(
+                f,
+                "Unicode character {:?} {} when case folded is a multicharacter String not a character",
+                ch, ch
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FreqError {}
+
+/// CountMap is implemented by the maps characters can be counted into:
+/// BTreeMap<char, usize> and, with the `std` feature, HashMap<char, usize, S>.
+pub trait CountMap: Default + IntoIterator<Item = (char, usize)> {
+    /// Adds frequency to the count of the character.
+    fn add(&mut self, character: char, frequency: usize);
+
+    /// Returns how many characters have a count.
+    fn len(&self) -> usize;
+
+    /// Returns whether no character has a count.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl CountMap for BTreeMap<char, usize> {
+    fn add(&mut self, character: char, frequency: usize) {
+        *self.entry(character).or_insert(0) += frequency;
+    }
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: BuildHasher + Default> CountMap for HashMap<char, usize, S> {
+    fn add(&mut self, character: char, frequency: usize) {
+        *self.entry(character).or_insert(0) += frequency;
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
+
+/// FrequencyMap wraps the frequencies of a text to display them as a table.
+///
+/// The table lists the characters by descending frequency, with ties in ascending
+/// order of the characters, followed by the total of all the frequencies.
+/// Characters are shown escaped, so control characters like '\t' can't break it.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = FrequencyMap::from(character_frequencies("Hello"));
+/// println!("{}", frequency_map);
+/// # assert_eq!(frequency_map.to_string(), "'l'    2\n'e'    1\n'h'    1\n'o'    1\ntotal  5\n");
+/// ```
+#[cfg(feature = "std")]
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct FrequencyMap(HashMap<char, usize>);
+
+#[cfg(feature = "std")]
+impl FrequencyMap {
+    /// Returns the wrapped frequencies.
+    pub fn into_inner(self) -> HashMap<char, usize> {
+        self.0
+    }
+
+    /// Iterates over the frequencies in descending order of frequency,
+    /// characters with the same frequency in ascending order of char.
+    ///
+    /// # Example
+    /// ```
+    /// use character_frequency::*;
+    /// let frequency_map = FrequencyMap::from(character_frequencies("Hello, World!"));
+    /// let top: Vec<(char, usize)> = frequency_map.iter_ranked().take(2).collect();
+    /// # assert_eq!(top, vec![('l', 3), ('o', 2)]);
+    /// ```
+    pub fn iter_ranked(&self) -> impl Iterator<Item = (char, usize)> {
+        let mut frequencies: Vec<(char, usize)> = self.0.iter().map(|(&c, &f)| (c, f)).collect();
+        frequencies.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        frequencies.into_iter()
+    }
+
+    /// Returns the sum of the frequencies of the characters that are the same as ch
+    /// ignoring their case, for frequencies counted with CaseSense::Sensitive.
+    ///
+    /// Two characters are the same if their lowercase or their uppercase is the same
+    /// character, so 'σ' also sums 'Σ' and the final sigma 'ς'. A case that converts to
+    /// several characters, like the uppercase "SS" of 'ß', is left as the character.
+    ///
+    /// # Example
+    /// ```
+    /// use character_frequency::*;
+    /// let frequency_map =
+    ///     FrequencyMap::from(character_frequencies_w_case("aAbB", CaseSense::Sensitive));
+    /// # assert_eq!(frequency_map.get_insensitive('a'), 2);
+    /// # assert_eq!(frequency_map.get_insensitive('B'), 2);
+    /// ```
+    pub fn get_insensitive(&self, ch: char) -> usize {
+        let lower = |ch| fold_char(ch, CaseSense::Insensitive).unwrap_or(ch);
+        let upper = |ch| fold_char(ch, CaseSense::InsensitiveUppercase).unwrap_or(ch);
+        let (ch_lower, ch_upper) = (lower(ch), upper(ch));
+        self.0
+            .iter()
+            .filter(|&(&other, _)| lower(other) == ch_lower || upper(other) == ch_upper)
+            .map(|(_, &frequency)| frequency)
+            .sum()
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<HashMap<char, usize>> for FrequencyMap {
+    fn from(frequency_map: HashMap<char, usize>) -> Self {
+        FrequencyMap(frequency_map)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<FrequencyMap> for HashMap<char, usize> {
+    fn from(frequency_map: FrequencyMap) -> Self {
+        frequency_map.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl Deref for FrequencyMap {
+    type Target = HashMap<char, usize>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for FrequencyMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<(String, usize)> = self
+            .iter_ranked()
+            .map(|(character, frequency)| (format!("'{}'", character.escape_debug()), frequency))
+            .collect();
+
+        let total: usize = self.0.values().sum();
+        let label = "total";
+        let character_width = rows
+            .iter()
+            .map(|row| row.0.chars().count())
+            .fold(label.len(), max);
+        let frequency_width = total.to_string().len();
+
+        for (character, frequency) in &rows {
+            writeln!(
+                f,
+                "{:<character_width$}  {:>frequency_width$}",
+                character, frequency
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<character_width$}  {:>frequency_width$}",
+            label, total
+        )
+    }
+}
+
+// Hasher of the maps used internally while counting. Char keys are small and
+// not attacker controlled, so a faster hasher than SipHash can be enabled with
+// the `fxhash` feature. The public functions always return a standard HashMap.
+#[cfg(feature = "fxhash")]
+type CharHasher = fxhash::FxBuildHasher;
+#[cfg(all(feature = "std", not(feature = "fxhash")))]
+type CharHasher = std::collections::hash_map::RandomState;
+
+#[cfg(feature = "std")]
+type CharMap = HashMap<char, usize, CharHasher>;
+
+/// StreamingCounter counts the frequencies of a text received in chunks, keeping the
+/// running frequencies between calls to push().
+///
+/// Chunks can be `&str` or raw bytes. A multibyte character split across two chunks is
+/// kept in an internal buffer until the rest of its bytes arrive. Bytes that are not valid
+/// UTF-8 are counted as U+FFFD REPLACEMENT CHARACTER, like String::from_utf8_lossy().
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let mut counter = StreamingCounter::new(CaseSense::Sensitive);
+/// counter.push("Hello, ");
+/// counter.push(&"Wörld".as_bytes()[..2]);
+/// counter.push(&"Wörld".as_bytes()[2..]);
+/// let frequency_map = counter.finish();
+/// # assert_eq!(frequency_map[&'ö'], 1);
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// ```
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct StreamingCounter {
+    case: CaseSense,
+    frequency_map: CharMap,
+    pending: Vec<u8>,
+}
+
+#[cfg(feature = "std")]
+impl StreamingCounter {
+    /// Creates a counter with no frequencies that converts the case with the CaseSense.
+    pub fn new(case: CaseSense) -> Self {
+        StreamingCounter {
+            case,
+            frequency_map: CharMap::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Counts the characters of the chunk, completing the ones split by the previous chunk.
+    pub fn push<T: AsRef<[u8]> + ?Sized>(&mut self, chunk: &T) {
+        let chunk = chunk.as_ref();
+        if self.pending.is_empty() {
+            let rest = self.count_valid(chunk);
+            self.pending.extend_from_slice(rest);
+        } else {
+            let mut bytes = std::mem::take(&mut self.pending);
+            bytes.extend_from_slice(chunk);
+            let rest = self.count_valid(&bytes).to_vec();
+            self.pending = rest;
+        }
+    }
+
+    /// Takes the characters of the chunk out of the frequencies, like subtract_frequencies(),
+    /// so a counter over a sliding window can drop the text leaving it. Frequencies saturate
+    /// at 0 and the characters left with a frequency of 0 are dropped.
+    ///
+    /// # Example
+    /// ```
+    /// use character_frequency::*;
+    /// let mut counter = StreamingCounter::new(CaseSense::Sensitive);
+    /// counter.push("old line\n");
+    /// counter.push("new line\n");
+    /// counter.remove("old line\n");
+    /// # assert_eq!(counter.frequencies(), character_frequencies_w_case("new line\n", CaseSense::Sensitive));
+    /// ```
+    pub fn remove(&mut self, chunk: &str) {
+        let mut removed = CharMap::default();
+        count_into(chunk, self.case, &mut removed);
+        for (character, frequency) in removed {
+            if let Some(count) = self.frequency_map.get_mut(&character) {
+                *count = count.saturating_sub(frequency);
+                if *count == 0 {
+                    self.frequency_map.remove(&character);
+                }
+            }
+        }
+    }
+
+    /// Returns the frequencies counted so far, leaving out a character still waiting for
+    /// the rest of its bytes.
+    pub fn frequencies(&self) -> HashMap<char, usize> {
+        self.frequency_map.iter().map(|(&c, &f)| (c, f)).collect()
+    }
+
+    /// Returns the frequencies counted on all the chunks. An incomplete character left at
+    /// the end is counted as U+FFFD REPLACEMENT CHARACTER.
+    pub fn finish(mut self) -> HashMap<char, usize> {
+        if !self.pending.is_empty() {
+            self.frequency_map.add(char::REPLACEMENT_CHARACTER, 1);
+        }
+        into_std_map(self.frequency_map)
+    }
+
+    // Counts the bytes up to an incomplete character at the end, which is returned.
+    fn count_valid<'a>(&mut self, mut bytes: &'a [u8]) -> &'a [u8] {
+        loop {
+            match std::str::from_utf8(bytes) {
+                Ok(text) => {
+                    count_into(text, self.case, &mut self.frequency_map);
+                    return &[];
+                }
+                Err(error) => {
+                    let (valid, rest) = bytes.split_at(error.valid_up_to());
+                    // SAFETY: from_utf8 checked that the bytes up to valid_up_to are valid UTF-8
+                    let text = unsafe { std::str::from_utf8_unchecked(valid) };
+                    count_into(text, self.case, &mut self.frequency_map);
+                    match error.error_len() {
+                        Some(invalid) => {
+                            self.frequency_map.add(char::REPLACEMENT_CHARACTER, 1);
+                            bytes = &rest[invalid..];
+                        }
+                        None => return rest,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Counts the frequencies of chars from a stream of byte chunks without blocking the async
+/// runtime, returning the first error of the stream.
+///
+/// The chunks are decoded like with StreamingCounter, so a multibyte character split across
+/// two chunks is counted once and invalid UTF-8 is counted as U+FFFD REPLACEMENT CHARACTER.
+/// Chunks of PARALLEL_THRESHOLD bytes or more are counted with tokio::task::spawn_blocking,
+/// so this has to be called from within a tokio runtime.
+///
+/// Requires the `tokio` feature.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let chunks = vec![Ok(Bytes::from("Hello, W")), Ok(Bytes::from("örld!"))];
+/// let frequency_map =
+///     character_frequencies_stream(futures::stream::iter(chunks), CaseSense::Sensitive)
+///         .await
+///         .unwrap();
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// # });
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn character_frequencies_stream<S>(
+    stream: S,
+    case: CaseSense,
+) -> io::Result<HashMap<char, usize>>
+where
+    S: Stream<Item = io::Result<Bytes>>,
+{
+    let mut stream = std::pin::pin!(stream);
+    let mut counter = StreamingCounter::new(case);
+    while let Some(chunk) = std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+        let chunk = chunk?;
+        if chunk.len() < PARALLEL_THRESHOLD {
+            counter.push(&chunk);
+            continue;
+        }
+        let counting = tokio::task::spawn_blocking(move || {
+            counter.push(&chunk);
+            counter
+        });
+        counter = match counting.await {
+            Ok(counter) => counter,
+            Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+            Err(error) => return Err(io::Error::other(error)),
+        };
+    }
+    Ok(counter.finish())
+}
+
+/// Normalization enables applying a Unicode normalization form before counting,
+/// so that canonically equivalent texts produce the same frequencies.
+/// Requires the `unicode-normalization` feature.
+/// * None - the text is counted as is. None is the default.
+/// * Nfc - characters are composed before counting, so 'e' followed by
+///   U+0301 COMBINING ACUTE ACCENT is counted as a single 'é'.
+/// * Nfd - characters are decomposed before counting, so 'é' is counted
+///   as an 'e' and a U+0301 COMBINING ACUTE ACCENT.
+/// * StripDiacritics - characters are decomposed and the combining marks
+///   of Latin letters are dropped, so 'á', 'à' and 'â' are all counted as 'a'.
+///   Combining marks of any other script are kept and the text is composed
+///   again as in Nfc, so Greek 'ά' is still counted as 'ά'.
+/// * StripAllDiacritics - same as StripDiacritics but dropping the combining
+///   marks of every script, so Greek 'ά' is counted as 'α'.
+/// * See also <https://unicode.org/reports/tr15/>
+#[cfg(feature = "unicode-normalization")]
+#[derive(Clone, Copy)]
+pub enum Normalization {
+    None,
+    Nfc,
+    Nfd,
+    StripDiacritics,
+    StripAllDiacritics,
 }
 
 /// Counts the frequencies of chars from a string with as many threads as cpu's.
@@ -58,8 +735,9 @@ pub enum CaseSense {
 /// # expected.insert(',', 1);
 /// # expected.insert(' ', 1);
 /// ```
-pub fn character_frequencies(text: &str) -> HashMap<char, usize> {
-    character_frequencies_with_n_threads(text, num_cpus::get())
+#[cfg(feature = "std")]
+pub fn character_frequencies(text: impl AsRef<str>) -> HashMap<char, usize> {
+    character_frequencies_w_case(text, CaseSense::InsensitiveASCIIOnly)
 }
 
 /// Same as character_frequences() but with Case Sensitivity
@@ -70,438 +748,4991 @@ pub fn character_frequencies(text: &str) -> HashMap<char, usize> {
 /// # use std::collections::HashMap;
 /// let frequency_map = character_frequencies_w_case("Hello, WORLD",CaseSense::Sensitive);
 /// ```
-pub fn character_frequencies_w_case(text: &str, case: CaseSense) -> HashMap<char, usize> {
-    character_frequencies_with_n_threads_w_case(text, num_cpus::get(), case)
+#[cfg(feature = "std")]
+pub fn character_frequencies_w_case(
+    text: impl AsRef<str>,
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    try_character_frequencies_w_case(text, case).unwrap()
 }
 
-/// Counts the frequencies of chars from a string with the amount of threads specified.
+/// Same as character_frequencies_w_case() but returning an error instead of panicking
 ///
-/// # Examples
+/// # Example
 /// ```
 /// use character_frequency::*;
-/// # use std::collections::HashMap;
+/// let result = try_character_frequencies_w_case("İstanbul", CaseSense::Insensitive);
+/// assert_eq!(result, Err(FreqError::MultiCharacterLowercase('İ')));
+/// ```
+#[cfg(feature = "std")]
+pub fn try_character_frequencies_w_case(
+    text: impl AsRef<str>,
+    case: CaseSense,
+) -> Result<HashMap<char, usize>, FreqError> {
+    let text = text.as_ref();
+    try_character_frequencies_with_n_threads_w_case(text, default_threads(), case)
+}
+
+/// Same as character_frequencies_w_case() but normalizing the text first
 ///
-/// let frequency_map = character_frequencies_with_n_threads("Hello, World!", 8);
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let frequency_map = character_frequencies_normalized(
+///     "cafe\u{301}",
+///     CaseSense::InsensitiveASCIIOnly,
+///     Normalization::Nfc,
+/// );
+/// # assert_eq!(frequency_map[&'é'], 1);
+/// ```
+#[cfg(feature = "unicode-normalization")]
+pub fn character_frequencies_normalized(
+    text: &str,
+    case: CaseSense,
+    normalization: Normalization,
+) -> HashMap<char, usize> {
+    character_frequencies_w_case(normalize(text, normalization), case)
+}
+
+#[cfg(feature = "unicode-normalization")]
+fn normalize(text: &str, normalization: Normalization) -> Cow<'_, str> {
+    match normalization {
+        Normalization::None => Cow::Borrowed(text),
+        Normalization::Nfc => Cow::Owned(text.nfc().collect()),
+        Normalization::Nfd => Cow::Owned(text.nfd().collect()),
+        Normalization::StripDiacritics => Cow::Owned(strip_diacritics(text, is_latin).collect()),
+        Normalization::StripAllDiacritics => Cow::Owned(strip_diacritics(text, |_| true).collect()),
+    }
+}
+
+// Decomposes the text and drops the combining marks following a base character
+// accepted by strip_base, composing what is left again.
+#[cfg(feature = "unicode-normalization")]
+fn strip_diacritics(text: &str, strip_base: fn(char) -> bool) -> impl Iterator<Item = char> + '_ {
+    let mut stripping = false;
+    text.nfd()
+        .filter(move |&ch| {
+            if unicode_normalization::char::is_combining_mark(ch) {
+                !stripping
+            } else {
+                stripping = strip_base(ch);
+                true
+            }
+        })
+        .nfc()
+}
+
+#[cfg(feature = "unicode-normalization")]
+fn is_latin(ch: char) -> bool {
+    matches!(ch,
+        'A'..='Z' | 'a'..='z'
+        | '\u{00C0}'..='\u{024F}'
+        | '\u{1E00}'..='\u{1EFF}'
+        | '\u{2C60}'..='\u{2C7F}'
+        | '\u{A720}'..='\u{A7FF}'
+        | '\u{AB30}'..='\u{AB6F}'
+    )
+}
+
+/// Counts the frequencies of the base characters of a string, counting each grapheme
+/// cluster once by its first char, so the combining marks attached to a letter are not
+/// counted on their own and "e\u{301}" counts as one 'e'.
 ///
-/// println!("Character frequencies:");
-/// for (character, frequency) in frequency_map {
-///     println!("\'{}\': {}", character, frequency);
-/// }
+/// Requires the `unicode-segmentation` feature. The CaseSense is applied to the base char.
 ///
-/// # let mut expected: HashMap<char, usize> = HashMap::new();
-/// # expected.insert('h', 1);
-/// # expected.insert('e', 1);
-/// # expected.insert('l', 3);
-/// # expected.insert('o', 2);
-/// # expected.insert('w', 1);
-/// # expected.insert('r', 1);
-/// # expected.insert('d', 1);
-/// # expected.insert('!', 1);
-/// # expected.insert(',', 1);
-/// # expected.insert(' ', 1);
+/// # Example
 /// ```
-pub fn character_frequencies_with_n_threads(text: &str, threads: usize) -> HashMap<char, usize> {
-    character_frequencies_with_n_threads_w_case(text, threads, CaseSense::InsensitiveASCIIOnly)
+/// use character_frequency::*;
+/// let frequency_map = base_character_frequencies("cafe\u{301}", CaseSense::Sensitive);
+/// # assert_eq!(frequency_map[&'e'], 1);
+/// # assert_eq!(frequency_map.get(&'\u{301}'), None);
+/// ```
+#[cfg(feature = "unicode-segmentation")]
+pub fn base_character_frequencies(text: &str, case: CaseSense) -> HashMap<char, usize> {
+    let mut frequency_map = CharMap::default();
+    for grapheme in text.graphemes(true) {
+        if let Some(base) = grapheme.chars().next() {
+            add_folded(&mut frequency_map, base, case, 1);
+        }
+    }
+    into_std_map(frequency_map)
 }
 
-/// same as character_frequencies_with_n_threads(), with Case Sensitivity
+/// Same as character_frequencies_w_case() but normalizing line endings first, so
+/// "\r\n" and lone "\r" are counted as a single "\n".
+///
+/// The whole text is normalized before it is split between threads, so a "\r\n"
+/// never gets counted as two characters.
+///
 /// # Example
 /// ```
 /// use character_frequency::*;
 /// # use std::collections::HashMap;
-/// let frequency_map = character_frequencies_with_n_threads_w_case("Hello, WORLD",2,CaseSense::Sensitive);
+/// let frequency_map =
+///     character_frequencies_normalized_newlines("Hello\r\nWorld\r\n", CaseSense::Sensitive);
+/// # assert_eq!(frequency_map[&'\n'], 2);
+/// # assert_eq!(frequency_map.get(&'\r'), None);
 /// ```
-pub fn character_frequencies_with_n_threads_w_case(
+#[cfg(feature = "std")]
+pub fn character_frequencies_normalized_newlines(
     text: &str,
-    threads: usize,
     case: CaseSense,
 ) -> HashMap<char, usize> {
-    if threads <= 1 {
-        return sequential_character_frequencies_w_case(text, case);
-    }
-
-    let (tx, rx) = mpsc::channel::<HashMap<char, usize>>();
-
-    let shared = Arc::new(String::from(text));
-    let chunk_size = max(1, text.len() / threads);
-
-    let threads_with_more_data = text.len() % threads;
-    let threads_with_less_data = threads - threads_with_more_data;
-
-    fn generate_counting_thread(
-        from: usize,
-        chunk_size: usize,
-        tx: &Sender<HashMap<char, usize>>,
-        shared: &Arc<String>,
-        case: CaseSense,
-    ) {
-        let tx = tx.clone();
-        let shared = shared.clone();
-        thread::spawn(move || {
-            let frequency_map =
-                character_frequencies_range(shared.as_str(), from, from + chunk_size - 1, case);
-            tx.send(frequency_map).unwrap();
-        });
+    character_frequencies_w_case(normalize_newlines(text), case)
+}
+
+#[cfg(feature = "std")]
+fn normalize_newlines(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Same as character_frequencies_w_case() but also returning the total amount of
+/// characters counted, which is the sum of all the frequencies.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let (frequency_map, total) =
+///     character_frequencies_with_total("Hello, WORLD", CaseSense::InsensitiveASCIIOnly);
+/// # assert_eq!(total, 12);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_with_total(
+    text: &str,
+    case: CaseSense,
+) -> (HashMap<char, usize>, usize) {
+    let frequency_map = character_frequencies_w_case(text, case);
+    let total = frequency_map.values().sum();
+    (frequency_map, total)
+}
+
+/// Same as character_frequencies_w_case() but also returning how the counting was split.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let (frequency_map, stats) = character_frequencies_with_stats("Hello", CaseSense::Sensitive);
+/// # assert!(stats.chunks >= 1 && stats.chunks <= 5);
+/// # assert_eq!(stats.chars, 5);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_with_stats(
+    text: &str,
+    case: CaseSense,
+) -> (HashMap<char, usize>, CountStats) {
+    Counter::new().case(case).count_with_stats(text)
+}
+
+/// Counts the frequencies of chars once with CaseSense::Sensitive, returning both those and
+/// the frequencies with the given CaseSense, obtained by converting the case of the
+/// counted characters instead of reading the text again.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let (sensitive, insensitive) =
+///     character_frequencies_both_cases("Hello, World!", CaseSense::InsensitiveASCIIOnly);
+/// # assert_eq!(sensitive[&'H'], 1);
+/// # assert_eq!(insensitive[&'h'], 1);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_both_cases(
+    text: &str,
+    case: CaseSense,
+) -> (HashMap<char, usize>, HashMap<char, usize>) {
+    let sensitive = character_frequencies_w_case(text, CaseSense::Sensitive);
+    let mut insensitive = HashMap::with_capacity(sensitive.len());
+    for (&character, &frequency) in &sensitive {
+        add_folded(&mut insensitive, character, case, frequency);
+    }
+    (sensitive, insensitive)
+}
+
+/// Counts the frequencies of chars with the CaseSense, also returning for each one the
+/// original character seen most often before converting its case.
+///
+/// Ties between the original characters resolve to the converted one, so with an insensitive
+/// CaseSense the same amount of 'A' and 'a' reports 'a'.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = character_frequencies_w_dominant_case("AAaBbb", CaseSense::Insensitive);
+/// # assert_eq!(frequency_map[&'a'], (3, 'A'));
+/// # assert_eq!(frequency_map[&'b'], (3, 'b'));
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_w_dominant_case(
+    text: &str,
+    case: CaseSense,
+) -> HashMap<char, (usize, char)> {
+    let mut dominant: HashMap<char, (usize, char, usize)> = HashMap::new();
+    for (original, frequency) in character_frequencies_w_case(text, CaseSense::Sensitive) {
+        let character = fold_case(original, case);
+        let entry = dominant.entry(character).or_insert((0, original, 0));
+        entry.0 += frequency;
+        let (_, best, best_frequency) = *entry;
+        let wins_tie = original == character || (best != character && original < best);
+        if frequency > best_frequency || (frequency == best_frequency && wins_tie) {
+            entry.1 = original;
+            entry.2 = frequency;
+        }
+    }
+    dominant
+        .into_iter()
+        .map(|(character, (frequency, original, _))| (character, (frequency, original)))
+        .collect()
+}
+
+/// Counts the frequencies of only the chars of a string that are in the alphabet, with as many
+/// threads as cpu's.
+///
+/// The characters are compared with the alphabet after applying the CaseSense, and every char
+/// of the alphabet is in the returned map, with frequency 0 if it is not in the text.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashSet;
+/// let alphabet: HashSet<char> = ['a', 'b', 'c'].into_iter().collect();
+/// let frequency_map =
+///     character_frequencies_within("Aabxyz", &alphabet, CaseSense::InsensitiveASCIIOnly);
+/// # assert_eq!(frequency_map.len(), 3);
+/// # assert_eq!(frequency_map[&'a'], 2);
+/// # assert_eq!(frequency_map[&'c'], 0);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_within(
+    text: &str,
+    alphabet: &HashSet<char>,
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    let mut frequency_map: HashMap<char, usize> = alphabet.iter().map(|&ch| (ch, 0)).collect();
+    for (character, frequency) in character_frequencies_w_case(text, case) {
+        if let Some(count) = frequency_map.get_mut(&character) {
+            *count += frequency;
+        }
+    }
+    frequency_map
+}
+
+/// Counts the frequencies of only the chars of a string that are in the range, with as many
+/// threads as cpu's.
+///
+/// The characters are compared with the range after applying the CaseSense, so with an
+/// insensitive CaseSense 'A' is counted as 'a' and is in 'a'..='z'.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map =
+///     character_frequencies_in_range("Hello, World!", 'a'..='z', CaseSense::InsensitiveASCIIOnly);
+/// # assert_eq!(frequency_map.values().sum::<usize>(), 10);
+/// # assert_eq!(frequency_map.get(&'!'), None);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_in_range(
+    text: &str,
+    range: RangeInclusive<char>,
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    let mut frequency_map = character_frequencies_w_case(text, case);
+    frequency_map.retain(|character, _| range.contains(character));
+    frequency_map
+}
+
+/// Counts the frequencies of the chars of a string matched by the regex, usually a character
+/// class like `\p{L}` or `[aeiou]`, with as many threads as cpu's. Matches longer than a
+/// single char are not counted. The CaseSense is applied to the chars after matching them.
+///
+/// Each thread searches its own part of the text, so patterns looking around the matched
+/// char, like word boundaries, may match differently next to the split points.
+///
+/// Requires the `regex` feature.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let vowels = Regex::new("[aeiou]").unwrap();
+/// let frequency_map =
+///     character_frequencies_matching("Hello, World!", &vowels, CaseSense::Sensitive);
+/// # assert_eq!(frequency_map[&'o'], 2);
+/// # assert_eq!(frequency_map.len(), 2);
+/// ```
+#[cfg(feature = "regex")]
+pub fn character_frequencies_matching(
+    text: &str,
+    class: &Regex,
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    let chunks = char_boundary_ranges(text, auto_threads(text));
+    let frequency_map = try_count_merged(chunks, |chunk| {
+        let matched = class
+            .find_iter(&text[chunk])
+            .filter_map(|found| single(found.as_str().chars()));
+        try_count_chars(matched, case, SkipSet::NONE)
+    });
+    into_std_map(frequency_map.unwrap())
+}
+
+/// Counts the frequencies of chars from a string with as many threads as cpu's, also returning
+/// the byte offsets of the first and last occurrence of each one, as (frequency, first, last).
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let positions = character_positions("Hello, World!", CaseSense::Sensitive);
+/// # assert_eq!(positions[&'l'], (3, 2, 10));
+/// # assert_eq!(positions[&'H'], (1, 0, 0));
+/// ```
+#[cfg(feature = "std")]
+pub fn character_positions(text: &str, case: CaseSense) -> HashMap<char, (usize, usize, usize)> {
+    let partials = run_each(char_boundary_ranges(text, auto_threads(text)), |range| {
+        let mut positions = HashMap::new();
+        for (index, ch) in text[range.clone()].char_indices() {
+            let offset = range.start + index;
+            let entry = positions
+                .entry(fold_case(ch, case))
+                .or_insert((0, offset, offset));
+            entry.0 += 1;
+            entry.2 = offset;
+        }
+        positions
+    });
+
+    let mut positions: HashMap<char, (usize, usize, usize)> = HashMap::new();
+    for partial in partials {
+        for (character, (frequency, first, last)) in partial {
+            let entry = positions.entry(character).or_insert((0, first, last));
+            entry.0 += frequency;
+            entry.1 = min(entry.1, first);
+            entry.2 = max(entry.2, last);
+        }
+    }
+    positions
+}
+
+/// Counts the frequencies of chars from a string with as many threads as cpu's, also returning
+/// the mean position of each one, as (frequency, mean). Positions are char indices divided by
+/// the index of the last char, so they go from 0.0 at the start of the text to 1.0 at its end.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let positions = mean_positions("abcab", CaseSense::Sensitive);
+/// # assert_eq!(positions[&'a'], (2, 0.375));
+/// # assert_eq!(positions[&'c'], (1, 0.5));
+/// ```
+#[cfg(feature = "std")]
+pub fn mean_positions(text: &str, case: CaseSense) -> HashMap<char, (usize, f64)> {
+    // each chunk counts its chars and their mean char index within the chunk
+    let partials = run_each(char_boundary_ranges(text, auto_threads(text)), |range| {
+        let mut positions: HashMap<char, (usize, f64)> = HashMap::new();
+        let mut chars = 0;
+        for (index, ch) in text[range].chars().enumerate() {
+            let entry = positions.entry(fold_case(ch, case)).or_insert((0, 0.0));
+            entry.0 += 1;
+            entry.1 += (index as f64 - entry.1) / entry.0 as f64;
+            chars = index + 1;
+        }
+        (positions, chars)
+    });
+
+    // the mean of a chunk is moved by the chars before it and weighted by its count
+    let total_chars: usize = partials.iter().map(|(_, chars)| chars).sum();
+    let last_index = max(1, total_chars.saturating_sub(1)) as f64;
+    let mut positions: HashMap<char, (usize, f64)> = HashMap::new();
+    let mut base = 0;
+    for (partial, chars) in partials {
+        for (character, (frequency, mean)) in partial {
+            let entry = positions.entry(character).or_insert((0, 0.0));
+            entry.0 += frequency;
+            entry.1 += frequency as f64 * (base as f64 + mean);
+        }
+        base += chars;
+    }
+    for (frequency, mean) in positions.values_mut() {
+        *mean /= *frequency as f64 * last_index;
+    }
+    positions
+}
+
+/// Counts the frequencies of chars from a string with as many threads as cpu's, returning them
+/// in descending order of frequency with ties ordered by the Tiebreak.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let ranked = ranked_frequencies("abba cc", CaseSense::Sensitive, Tiebreak::ByFirstOccurrence);
+/// # assert_eq!(ranked, vec![('a', 2), ('b', 2), ('c', 2), (' ', 1)]);
+/// ```
+#[cfg(feature = "std")]
+pub fn ranked_frequencies(text: &str, case: CaseSense, tiebreak: Tiebreak) -> Vec<(char, usize)> {
+    match tiebreak {
+        Tiebreak::ByChar => FrequencyMap::from(character_frequencies_w_case(text, case))
+            .iter_ranked()
+            .collect(),
+        Tiebreak::ByFirstOccurrence => {
+            let mut ranked: Vec<(char, usize, usize)> = character_positions(text, case)
+                .into_iter()
+                .map(|(character, (frequency, first, _))| (character, frequency, first))
+                .collect();
+            ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.2.cmp(&b.2)));
+            ranked
+                .into_iter()
+                .map(|(character, frequency, _)| (character, frequency))
+                .collect()
+        }
+    }
+}
+
+/// Counts the frequencies of the UTF-16 code units of a string instead of its chars.
+/// Characters outside the Basic Multilingual Plane are encoded as a surrogate pair,
+/// so they add to the frequency of two code units.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = utf16_unit_frequencies("a𝄞");
+/// # assert_eq!(frequency_map[&0x0061], 1);
+/// # assert_eq!(frequency_map[&0xD834], 1);
+/// # assert_eq!(frequency_map[&0xDD1E], 1);
+/// ```
+#[cfg(feature = "std")]
+pub fn utf16_unit_frequencies(text: &str) -> HashMap<u16, usize> {
+    let mut frequency_map = HashMap::new();
+    for unit in text.encode_utf16() {
+        *frequency_map.entry(unit).or_insert(0) += 1;
+    }
+    frequency_map
+}
+
+/// Counts the occurrences of a single character in a string with as many threads as cpu's,
+/// or a single one for texts shorter than PARALLEL_THRESHOLD bytes, without building a
+/// frequency map.
+///
+/// The CaseSense is applied to both the target and the text, so with an insensitive
+/// CaseSense counting 'A' also counts every 'a'.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency = count_char("Hello, World!", 'L', CaseSense::InsensitiveASCIIOnly);
+/// # assert_eq!(frequency, 3);
+/// ```
+#[cfg(feature = "std")]
+pub fn count_char(text: &str, target: char, case: CaseSense) -> usize {
+    let target = fold_case(target, case);
+    run_each(char_boundary_ranges(text, auto_threads(text)), |range| {
+        text[range]
+            .chars()
+            .filter(|&ch| fold_case(ch, case) == target)
+            .count()
+    })
+    .into_iter()
+    .sum()
+}
+
+/// Counts the ASCII letters of a string ignoring their case, with as many threads as cpu's,
+/// into an array with the frequency of 'a' at index 0 up to the one of 'z' at index 25.
+/// Every other character is ignored, and no map is built.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let letters = count_ascii_lowercase("Hello, World!");
+/// # assert_eq!(letters[(b'l' - b'a') as usize], 3);
+/// # assert_eq!(letters.iter().sum::<usize>(), 10);
+/// ```
+#[cfg(feature = "std")]
+pub fn count_ascii_lowercase(text: &str) -> [usize; 26] {
+    let partials = run_each(char_boundary_ranges(text, auto_threads(text)), |range| {
+        let mut letters = [0; 26];
+        for byte in text[range].bytes() {
+            if byte.is_ascii_alphabetic() {
+                letters[(byte.to_ascii_lowercase() - b'a') as usize] += 1;
+            }
+        }
+        letters
+    });
+    let mut letters = [0; 26];
+    for partial in partials {
+        for (total, frequency) in letters.iter_mut().zip(partial) {
+            *total += frequency;
+        }
+    }
+    letters
+}
+
+/// Counts how many different chars a string has after applying the CaseSense, with as many
+/// threads as cpu's, without counting their frequencies.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let distinct = distinct_char_count("Hello, World!", CaseSense::InsensitiveASCIIOnly);
+/// # assert_eq!(distinct, 10);
+/// ```
+#[cfg(feature = "std")]
+pub fn distinct_char_count(text: &str, case: CaseSense) -> usize {
+    let partials = run_each(char_boundary_ranges(text, auto_threads(text)), |range| {
+        text[range]
+            .chars()
+            .map(|ch| fold_case(ch, case))
+            .collect::<HashSet<char>>()
+    });
+    let mut distinct = HashSet::new();
+    for mut partial in partials {
+        if partial.len() > distinct.len() {
+            std::mem::swap(&mut distinct, &mut partial);
+        }
+        distinct.extend(partial);
+    }
+    distinct.len()
+}
+
+/// Counts how many chars of a string belong to each Unicode General Category,
+/// with as many threads as cpu's.
+///
+/// Requires the `unicode-properties` feature.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = category_frequencies("Hello, World!");
+/// # assert_eq!(frequency_map[&GeneralCategory::LowercaseLetter], 8);
+/// # assert_eq!(frequency_map[&GeneralCategory::OtherPunctuation], 2);
+/// ```
+#[cfg(feature = "unicode-properties")]
+pub fn category_frequencies(text: &str) -> HashMap<GeneralCategory, usize> {
+    frequencies_by_key(text, |ch| ch.general_category())
+}
+
+/// Counts how many chars of a string belong to each Unicode Script, with as many threads
+/// as cpu's.
+///
+/// Characters shared by several scripts, like digits, punctuation and spaces, are counted
+/// as Script::Common, and combining marks taking the script of their base character as
+/// Script::Inherited.
+///
+/// Requires the `unicode-script` feature.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = script_frequencies("Hello, κόσμε!");
+/// # assert_eq!(frequency_map[&Script::Latin], 5);
+/// # assert_eq!(frequency_map[&Script::Greek], 5);
+/// # assert_eq!(frequency_map[&Script::Common], 3);
+/// ```
+#[cfg(feature = "unicode-script")]
+pub fn script_frequencies(text: &str) -> HashMap<Script, usize> {
+    frequencies_by_key(text, |ch| ch.script())
+}
+
+/// Counts how many chars of a string belong to each Unicode block, like "Basic Latin",
+/// "Greek and Coptic" or "CJK Unified Ideographs", with as many threads as cpu's.
+///
+/// Characters outside of every block are counted as "No_Block".
+///
+/// Requires the `unicode-blocks` feature.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = block_frequencies("Hello, κόσμε!");
+/// # assert_eq!(frequency_map["Basic Latin"], 8);
+/// # assert_eq!(frequency_map["Greek and Coptic"], 5);
+/// ```
+#[cfg(feature = "unicode-blocks")]
+pub fn block_frequencies(text: &str) -> HashMap<&'static str, usize> {
+    frequencies_by_key(text, |ch| match unicode_blocks::find_unicode_block(ch) {
+        Some(block) => block.name(),
+        None => "No_Block",
+    })
+}
+
+/// Counts the frequency of the chars in a string with as many threads as cpu's, and
+/// tells whether the text mixes characters of more than one Unicode Script.
+///
+/// Script::Common and Script::Inherited characters, like digits, punctuation, spaces and
+/// combining marks, don't count as a script of their own, so mixing is only reported when
+/// letters of different scripts appear, like a Cyrillic 'а' among Latin letters. Each
+/// script is looked up once per distinct character after counting, not once per char.
+///
+/// Requires the `unicode-script` feature.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let (frequency_map, mixed) = character_frequencies_with_script_check("p\u{430}ypal.com");
+/// # assert_eq!(frequency_map[&'p'], 2);
+/// # assert_eq!(frequency_map[&'\u{430}'], 1);
+/// # assert!(mixed);
+/// ```
+#[cfg(feature = "unicode-script")]
+pub fn character_frequencies_with_script_check(text: &str) -> (HashMap<char, usize>, bool) {
+    let frequency_map = character_frequencies_w_case(text, CaseSense::Sensitive);
+    let mut scripts = frequency_map
+        .keys()
+        .map(|ch| ch.script())
+        .filter(|script| !matches!(script, Script::Common | Script::Inherited));
+    let mixed = match scripts.next() {
+        Some(first) => scripts.any(|script| script != first),
+        None => false,
+    };
+    (frequency_map, mixed)
+}
+
+/// Counts how many chars of a string the classify function puts in each group, with as
+/// many threads as cpu's.
+///
+/// Characters that shouldn't be counted can be put in a group that is then removed
+/// from the result.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let mut groups = grouped_frequencies("Hello, World!", |ch| match ch.to_ascii_lowercase() {
+///     'a' | 'e' | 'i' | 'o' | 'u' => "vowel",
+///     'a'..='z' => "consonant",
+///     _ => "ignore",
+/// });
+/// groups.remove("ignore");
+/// # assert_eq!(groups["vowel"], 3);
+/// # assert_eq!(groups["consonant"], 7);
+/// # assert_eq!(groups.len(), 2);
+/// ```
+#[cfg(feature = "std")]
+pub fn grouped_frequencies(
+    text: &str,
+    classify: impl Fn(char) -> &'static str + Sync,
+) -> HashMap<&'static str, usize> {
+    frequencies_by_key(text, classify)
+}
+
+/// Counts how many chars of a string are mapped to each key by the key function,
+/// with as many threads as cpu's, or a single one for texts shorter than
+/// PARALLEL_THRESHOLD bytes.
+///
+/// The CaseSense functions are the same as a key function converting the case,
+/// but any grouping of the characters can be counted.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = frequencies_by_key("Hello, World!", |ch| match ch.to_ascii_lowercase() {
+///     'a' | 'e' | 'i' | 'o' | 'u' => 'V',
+///     _ => ch,
+/// });
+/// # assert_eq!(frequency_map[&'V'], 3);
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// ```
+#[cfg(feature = "std")]
+pub fn frequencies_by_key<K, F>(text: &str, key: F) -> HashMap<K, usize>
+where
+    K: Eq + Hash + Send,
+    F: Fn(char) -> K + Sync,
+{
+    frequencies_by_key_with_n_threads(text, auto_threads(text), key)
+}
+
+// Same as frequencies_by_key but splitting the text between the given amount of threads.
+#[cfg(feature = "std")]
+fn frequencies_by_key_with_n_threads<K, F>(text: &str, threads: usize, key: F) -> HashMap<K, usize>
+where
+    K: Eq + Hash + Send,
+    F: Fn(char) -> K + Sync,
+{
+    let partials = run_each(char_boundary_ranges(text, threads), |range| {
+        let mut frequency_map = HashMap::new();
+        for key in text[range].chars().map(&key) {
+            *frequency_map.entry(key).or_insert(0) += 1;
+        }
+        frequency_map
+    });
+
+    let mut frequency_map = HashMap::new();
+    for partial in partials {
+        for (key, frequency) in partial {
+            *frequency_map.entry(key).or_insert(0) += frequency;
+        }
+    }
+    frequency_map
+}
+
+// Splits the text into at most parts non empty byte ranges of similar length,
+// moving each split point back to just after a nearby '\n', or to the previous
+// char boundary if there is none. Only the bytes around the split points are read.
+#[cfg(feature = "std")]
+fn char_boundary_ranges(text: &str, parts: usize) -> Vec<Range<usize>> {
+    utf8_boundary_ranges(text.as_bytes(), parts)
+}
+
+// Same as char_boundary_ranges but over bytes that may not be valid UTF-8, where
+// a char boundary is any byte that is not a UTF-8 continuation byte.
+#[cfg(feature = "std")]
+fn utf8_boundary_ranges(bytes: &[u8], parts: usize) -> Vec<Range<usize>> {
+    let parts = max(1, min(parts, bytes.len()));
+    let mut ranges = Vec::with_capacity(parts);
+    let mut from = 0;
+    for part in 1..=parts {
+        let to = if part == parts {
+            bytes.len()
+        } else {
+            split_point(bytes, from, bytes.len() * part / parts)
+        };
+        if to > from {
+            ranges.push(from..to);
+            from = to;
+        }
+    }
+    ranges
+}
+
+// How many bytes before a split point are searched for a '\n' to split after.
+#[cfg(feature = "std")]
+const NEWLINE_WINDOW: usize = 256;
+
+// Returns the split point at or before target and after from, preferring to split after a line.
+#[cfg(feature = "std")]
+fn split_point(bytes: &[u8], from: usize, target: usize) -> usize {
+    let window_start = max(from, target.saturating_sub(NEWLINE_WINDOW));
+    if let Some(newline) = bytes[window_start..target]
+        .iter()
+        .rposition(|&b| b == b'\n')
+    {
+        return window_start + newline + 1;
+    }
+    let mut to = target;
+    while to > from && bytes[to] & 0b1100_0000 == 0b1000_0000 {
+        to -= 1;
+    }
+    to
+}
+
+/// Same as character_frequencies_w_case() but returning a BTreeMap, so iterating
+/// over the frequencies is always in ascending order of the characters.
+///
+/// Without the `std` feature the characters are counted sequentially.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = character_frequencies_sorted("Hello", CaseSense::Sensitive);
+/// # assert_eq!(frequency_map.keys().collect::<String>(), "Helo");
+/// ```
+pub fn character_frequencies_sorted(text: &str, case: CaseSense) -> BTreeMap<char, usize> {
+    #[cfg(feature = "std")]
+    return character_frequencies_w_case(text, case)
+        .into_iter()
+        .collect();
+    #[cfg(not(feature = "std"))]
+    return sequential_character_frequencies_w_case(text, case);
+}
+
+/// Counts the frequencies of chars from a string with the amount of threads specified.
+///
+/// Each thread counts its part of the text into its own map, which is added into the
+/// largest map so far in the order the threads finish. So besides the returned map, only the
+/// maps of the threads still counting and of the ones waiting to be added are kept in memory.
+/// The maps are added on the calling thread whatever the amount of threads, as starting a
+/// thread for each addition would take longer than the addition.
+///
+/// # Examples
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+///
+/// let frequency_map = character_frequencies_with_n_threads("Hello, World!", 8);
+///
+/// println!("Character frequencies:");
+/// for (character, frequency) in frequency_map {
+///     println!("\'{}\': {}", character, frequency);
+/// }
+///
+/// # let mut expected: HashMap<char, usize> = HashMap::new();
+/// # expected.insert('h', 1);
+/// # expected.insert('e', 1);
+/// # expected.insert('l', 3);
+/// # expected.insert('o', 2);
+/// # expected.insert('w', 1);
+/// # expected.insert('r', 1);
+/// # expected.insert('d', 1);
+/// # expected.insert('!', 1);
+/// # expected.insert(',', 1);
+/// # expected.insert(' ', 1);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_with_n_threads(
+    text: impl AsRef<str>,
+    threads: usize,
+) -> HashMap<char, usize> {
+    character_frequencies_with_n_threads_w_case(text, threads, CaseSense::InsensitiveASCIIOnly)
+}
+
+/// same as character_frequencies_with_n_threads(), with Case Sensitivity
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let frequency_map = character_frequencies_with_n_threads_w_case("Hello, WORLD",2,CaseSense::Sensitive);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_with_n_threads_w_case(
+    text: impl AsRef<str>,
+    threads: usize,
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    try_character_frequencies_with_n_threads_w_case(text, threads, case).unwrap()
+}
+
+/// Same as character_frequencies_with_n_threads_w_case() but returning an error
+/// instead of panicking
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let result = try_character_frequencies_with_n_threads_w_case("İ", 2, CaseSense::Insensitive);
+/// assert_eq!(result, Err(FreqError::MultiCharacterLowercase('İ')));
+/// ```
+#[cfg(feature = "std")]
+pub fn try_character_frequencies_with_n_threads_w_case(
+    text: impl AsRef<str>,
+    threads: usize,
+    case: CaseSense,
+) -> Result<HashMap<char, usize>, FreqError> {
+    try_parallel_frequencies(text.as_ref(), threads, case, SkipSet::NONE).map(into_std_map)
+}
+
+/// Same as character_frequencies_w_case() but leaving out the characters in the SkipSet
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let frequency_map = character_frequencies_skipping(
+///     "Hello, WORLD",
+///     CaseSense::Sensitive,
+///     SkipSet::SKIP_WHITESPACE | SkipSet::SKIP_PUNCTUATION,
+/// );
+/// # assert_eq!(frequency_map.get(&' '), None);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_skipping(
+    text: &str,
+    case: CaseSense,
+    skip: SkipSet,
+) -> HashMap<char, usize> {
+    try_character_frequencies_skipping(text, case, skip).unwrap()
+}
+
+/// Same as character_frequencies_skipping() but returning an error instead of panicking
+#[cfg(feature = "std")]
+pub fn try_character_frequencies_skipping(
+    text: &str,
+    case: CaseSense,
+    skip: SkipSet,
+) -> Result<HashMap<char, usize>, FreqError> {
+    try_parallel_frequencies(text, default_threads(), case, skip).map(into_std_map)
+}
+
+#[cfg(feature = "std")]
+fn try_parallel_frequencies(
+    text: &str,
+    threads: usize,
+    case: CaseSense,
+    skip: SkipSet,
+) -> Result<CharMap, FreqError> {
+    try_parallel_frequencies_with_stats(text, threads, case, skip).map(|(map, _)| map)
+}
+
+#[cfg(feature = "std")]
+fn try_parallel_frequencies_with_stats(
+    text: &str,
+    threads: usize,
+    case: CaseSense,
+    skip: SkipSet,
+) -> Result<(CharMap, CountStats), FreqError> {
+    let counted = AtomicUsize::new(0);
+    let count_chunk = |chunk: Range<usize>| {
+        let mut chars = 0;
+        let frequency_map =
+            try_count_chars(text[chunk].chars().inspect(|_| chars += 1), case, skip);
+        counted.fetch_add(chars, Ordering::Relaxed);
+        frequency_map
+    };
+    if threads <= 1 {
+        let frequency_map = count_chunk(0..text.len())?;
+        return Ok(with_stats(frequency_map, 1, 1, counted.into_inner()));
+    }
+
+    // one chunk per thread split at char boundaries, never more chunks than chars
+    let chunks = char_boundary_ranges(text, threads);
+    let frequency_map = try_count_merged(chunks.clone(), count_chunk)?;
+    Ok(with_stats(
+        frequency_map,
+        chunks.len(),
+        chunks.len(),
+        counted.into_inner(),
+    ))
+}
+
+// Runs the counting task on each item, adding each map into the largest one so far in the
+// order the threads finish. Besides that map, only the maps of the threads still counting and
+// of the ones finished but not yet added are kept in memory, instead of all of them. Returns
+// the first error of any of the tasks.
+// There is no tree of adding threads for any amount of threads, not only for 2 or 3: starting
+// a thread per addition costs more than the addition itself, which the merge benchmarks show
+// for 16 maps as much as for 2 or 3.
+#[cfg(feature = "std")]
+fn try_count_merged<T, F>(items: Vec<T>, task: F) -> Result<CharMap, FreqError>
+where
+    T: Send,
+    F: Fn(T) -> Result<CharMap, FreqError> + Sync,
+{
+    let mut frequency_map = CharMap::default();
+    let mut first_error = None;
+    for_each_completed(items, task, |result| {
+        match result.unwrap_or_else(|panic| std::panic::resume_unwind(panic)) {
+            Ok(partial) if first_error.is_none() => {
+                frequency_map = add_frequencies(std::mem::take(&mut frequency_map), partial);
+            }
+            Ok(_) => {}
+            Err(error) => {
+                first_error.get_or_insert(error);
+            }
+        }
+    });
+    match first_error {
+        Some(error) => Err(error),
+        None => Ok(frequency_map),
+    }
+}
+
+// Runs the task on each item on its own scoped thread, returning the results in the
+// order of the items. If a task panics its panic is resumed on the calling thread, with
+// the original payload, once every thread has finished.
+#[cfg(feature = "std")]
+fn run_each<T, R, F>(items: Vec<T>, task: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let mut results = Vec::with_capacity(items.len());
+    for_each_result(items, task, |result| {
+        results.push(result.unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+    });
+    results
+}
+
+// Runs the task on each item on its own scoped thread, passing the results to on_result
+// in the order of the items as the threads are joined.
+#[cfg(all(
+    feature = "std",
+    not(any(
+        feature = "single-thread",
+        all(target_family = "wasm", not(target_feature = "atomics"))
+    ))
+))]
+fn for_each_result<T, R, F>(items: Vec<T>, task: F, mut on_result: impl FnMut(thread::Result<R>))
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let task = &task;
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .into_iter()
+            .map(|item| scope.spawn(move || task(item)))
+            .collect();
+        for handle in handles {
+            on_result(handle.join());
+        }
+    })
+}
+
+// Same as for_each_result but passing the results in the order the threads finish, each
+// thread sending its result to the calling thread, so a slow thread doesn't hold back the
+// results of the ones after it.
+#[cfg(all(
+    feature = "std",
+    not(any(
+        feature = "single-thread",
+        all(target_family = "wasm", not(target_feature = "atomics"))
+    ))
+))]
+fn for_each_completed<T, R, F>(items: Vec<T>, task: F, mut on_result: impl FnMut(thread::Result<R>))
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let task = &task;
+    let (sender, receiver) = std::sync::mpsc::channel();
+    thread::scope(|scope| {
+        let count = items.len();
+        for item in items {
+            let sender = sender.clone();
+            scope.spawn(move || {
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| task(item)));
+                // the receiver outlives the scope, so the result is always received
+                let _ = sender.send(result);
+            });
+        }
+        for result in receiver.iter().take(count) {
+            on_result(result);
+        }
+    })
+}
+
+// Same as for_each_result but running the tasks one after another on the calling thread,
+// for targets without threads. A panicking task unwinds into the caller.
+#[cfg(all(
+    feature = "std",
+    any(
+        feature = "single-thread",
+        all(target_family = "wasm", not(target_feature = "atomics"))
+    )
+))]
+fn for_each_result<T, R, F>(items: Vec<T>, task: F, mut on_result: impl FnMut(thread::Result<R>))
+where
+    F: Fn(T) -> R,
+{
+    for item in items {
+        on_result(Ok(task(item)));
+    }
+}
+
+// Same as for_each_result, the tasks finishing in the order of the items on the calling thread.
+#[cfg(all(
+    feature = "std",
+    any(
+        feature = "single-thread",
+        all(target_family = "wasm", not(target_feature = "atomics"))
+    )
+))]
+fn for_each_completed<T, R, F>(items: Vec<T>, task: F, on_result: impl FnMut(thread::Result<R>))
+where
+    F: Fn(T) -> R,
+{
+    for_each_result(items, task, on_result)
+}
+
+// Pairs the frequencies with the stats of how they were counted, chars being the amount of
+// chars of the text that were read, not the total of the frequencies.
+#[cfg(feature = "std")]
+fn with_stats(
+    frequency_map: CharMap,
+    threads: usize,
+    chunks: usize,
+    chars: usize,
+) -> (CharMap, CountStats) {
+    let stats = CountStats {
+        threads,
+        chunks,
+        chars,
+    };
+    (frequency_map, stats)
+}
+
+/// Merges all the frequency maps into one, adding up the frequencies of the chars present in
+/// several of them. The maps are added into the one with the most characters, so it is the
+/// only one that may need to grow.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = merge_all(["Hello", ", ", "World"].map(character_frequencies));
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// # assert_eq!(frequency_map[&' '], 1);
+/// ```
+#[cfg(feature = "std")]
+pub fn merge_all<I: IntoIterator<Item = HashMap<char, usize>>>(maps: I) -> HashMap<char, usize> {
+    merge_into_largest(maps.into_iter().collect())
+}
+
+// Folds all the maps into the one with the most entries, so it is the only one that may grow.
+#[cfg(feature = "std")]
+fn merge_into_largest<S: BuildHasher + Default>(
+    mut maps: Vec<HashMap<char, usize, S>>,
+) -> HashMap<char, usize, S> {
+    let largest = match (0..maps.len()).max_by_key(|&i| maps[i].len()) {
+        Some(largest) => largest,
+        None => return HashMap::default(),
+    };
+    let base = maps.swap_remove(largest);
+    maps.into_iter().fold(base, add_frequencies)
+}
+
+/// Counter configures how the frequencies of a text are counted.
+/// * threads - how many threads count at the same time. Defaults to as many as cpu's.
+/// * case - the CaseSense applied to the characters. Defaults to InsensitiveASCIIOnly.
+/// * chunk_chars - how many characters each counting task takes. By default the text is
+///   split in one chunk per thread, with it the text is split in ceil(chars / chunk_chars)
+///   chunks that are distributed over the threads.
+/// * tasks - how many chunks of similar length the text is split in, distributed over the
+///   threads as they finish, so a few chunks of slow to count characters don't keep a
+///   single thread busy. An alternative to chunk_chars, setting one unsets the other.
+/// * collapse_whitespace - counts every run of whitespace characters as a single ' ',
+///   so "a  \tb" counts one ' '. Disabled by default.
+/// * deterministic_order - each chunk is counted into its own map, and the maps are merged
+///   in the order of the chunks once all of them are counted, so the merging is the same on
+///   every run. Disabled by default, when the threads merge the chunks in the order they
+///   take them.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = Counter::new()
+///     .threads(4)
+///     .case(CaseSense::Sensitive)
+///     .chunk_chars(1 << 16)
+///     .count("Hello, World!");
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// ```
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct Counter {
+    threads: Option<usize>,
+    case: CaseSense,
+    chunk_chars: Option<usize>,
+    tasks: Option<usize>,
+    collapse_whitespace: bool,
+    deterministic_order: bool,
+}
+
+#[cfg(feature = "std")]
+impl Default for Counter {
+    fn default() -> Self {
+        Counter {
+            threads: None,
+            case: CaseSense::InsensitiveASCIIOnly,
+            chunk_chars: None,
+            tasks: None,
+            collapse_whitespace: false,
+            deterministic_order: false,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Counter {
+    /// Creates a Counter with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how many threads count at the same time.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Sets the CaseSense applied to the characters.
+    pub fn case(mut self, case: CaseSense) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// Sets how many characters each counting task takes, at least 1.
+    pub fn chunk_chars(mut self, chunk_chars: usize) -> Self {
+        self.chunk_chars = Some(max(1, chunk_chars));
+        self.tasks = None;
+        self
+    }
+
+    /// Sets how many counting tasks the text is split in, at least 1.
+    pub fn tasks(mut self, tasks: usize) -> Self {
+        self.tasks = Some(max(1, tasks));
+        self.chunk_chars = None;
+        self
+    }
+
+    /// Sets whether every run of whitespace characters is counted as a single ' '.
+    pub fn collapse_whitespace(mut self, collapse_whitespace: bool) -> Self {
+        self.collapse_whitespace = collapse_whitespace;
+        self
+    }
+
+    /// Sets whether the chunks are merged in their order in the text.
+    pub fn deterministic_order(mut self, deterministic_order: bool) -> Self {
+        self.deterministic_order = deterministic_order;
+        self
+    }
+
+    /// Counts the frequencies of chars in the text.
+    pub fn count(&self, text: &str) -> HashMap<char, usize> {
+        self.try_count(text).unwrap()
+    }
+
+    /// Same as count() but returning an error instead of panicking.
+    pub fn try_count(&self, text: &str) -> Result<HashMap<char, usize>, FreqError> {
+        self.try_count_with_stats(text)
+            .map(|(frequency_map, _)| frequency_map)
+    }
+
+    /// Same as count() but also returning how the counting was split.
+    pub fn count_with_stats(&self, text: &str) -> (HashMap<char, usize>, CountStats) {
+        self.try_count_with_stats(text).unwrap()
+    }
+
+    /// Same as count_with_stats() but returning an error instead of panicking.
+    pub fn try_count_with_stats(
+        &self,
+        text: &str,
+    ) -> Result<(HashMap<char, usize>, CountStats), FreqError> {
+        if self.deterministic_order {
+            return self
+                .try_count_ordered(text)
+                .map(|(frequency_map, _, stats)| (into_std_map(frequency_map), stats));
+        }
+        let threads = self.threads.unwrap_or_else(default_threads);
+        let chunks = match (self.chunk_chars, self.tasks, self.collapse_whitespace) {
+            (Some(chunk_chars), _, _) => char_chunks(text, chunk_chars),
+            (None, Some(tasks), _) => char_boundary_ranges(text, tasks),
+            (None, None, true) => char_boundary_ranges(text, threads),
+            (None, None, false) => {
+                return try_parallel_frequencies_with_stats(
+                    text,
+                    threads,
+                    self.case,
+                    SkipSet::NONE,
+                )
+                .map(|(frequency_map, stats)| (into_std_map(frequency_map), stats));
+            }
+        };
+        try_chunked_frequencies(text, &chunks, threads, self.case, self.collapse_whitespace)
+            .map(|(frequency_map, stats)| (into_std_map(frequency_map), stats))
+    }
+
+    /// Counts the frequencies of chars in the text, returning them in descending order of
+    /// frequency with ties ordered by the Tiebreak. Ranking by first occurrence always
+    /// merges the chunks in their order, as with deterministic_order.
+    ///
+    /// # Example
+    /// ```
+    /// use character_frequency::*;
+    /// let ranked = Counter::new()
+    ///     .threads(2)
+    ///     .count_ranked("zzaa b", Tiebreak::ByFirstOccurrence);
+    /// # assert_eq!(ranked, vec![('z', 2), ('a', 2), (' ', 1), ('b', 1)]);
+    /// ```
+    pub fn count_ranked(&self, text: &str, tiebreak: Tiebreak) -> Vec<(char, usize)> {
+        match tiebreak {
+            Tiebreak::ByChar => FrequencyMap::from(self.count(text)).iter_ranked().collect(),
+            Tiebreak::ByFirstOccurrence => {
+                let (frequency_map, order, _) = self.try_count_ordered(text).unwrap();
+                let mut ranked: Vec<(char, usize)> = order
+                    .into_iter()
+                    .map(|character| (character, frequency_map[&character]))
+                    .collect();
+                // stable, so ties keep the order of first occurrence
+                ranked.sort_by_key(|&(_, frequency)| std::cmp::Reverse(frequency));
+                ranked
+            }
+        }
+    }
+
+    // Counts every chunk into its own map, merged in the order of the chunks. Also returns
+    // the characters in the order they first appear in the text.
+    fn try_count_ordered(&self, text: &str) -> Result<(CharMap, Vec<char>, CountStats), FreqError> {
+        let threads = self.threads.unwrap_or_else(default_threads);
+        let chunks = match (self.chunk_chars, self.tasks) {
+            (Some(chunk_chars), _) => char_chunks(text, chunk_chars),
+            (None, Some(tasks)) => char_boundary_ranges(text, tasks),
+            (None, None) => char_boundary_ranges(text, threads),
+        };
+        try_ordered_chunked_frequencies(text, &chunks, threads, self.case, self.collapse_whitespace)
+    }
+}
+
+/// CountOptions holds the options of count(), built from its Default with the methods
+/// named after each option.
+/// * threads - how many threads count at the same time. Defaults to as many as cpu's,
+///   or 1 for texts shorter than PARALLEL_THRESHOLD bytes.
+/// * case - the CaseSense applied to the characters. Defaults to InsensitiveASCIIOnly.
+/// * normalization - the Normalization applied to the text before counting. Defaults to
+///   Normalization::None. Requires the `unicode-normalization` feature.
+/// * filter - only the characters it returns true for are counted, checked before
+///   applying the CaseSense. By default every character is counted.
+/// * expansion - the Expansion applied to the characters whose case converts to several.
+///   Defaults to Expansion::Expand, so count() never panics on them.
+/// * sequential_merge - the maps counted by the threads are merged only after all of them
+///   finished counting, so counting and merging can be timed apart, at the cost of keeping
+///   every map in memory until then. By default each map is merged as soon as its thread
+///   finishes, while the others are still counting. Either way the maps are merged on the
+///   calling thread, there is no parallel merge to turn off.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let options = CountOptions::default()
+///     .threads(2)
+///     .case(CaseSense::Sensitive)
+///     .filter(|ch| !ch.is_whitespace());
+/// let frequency_map = count("Hello, World!", &options);
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// # assert_eq!(frequency_map.get(&' '), None);
+/// ```
+#[cfg(feature = "std")]
+#[derive(Clone, Copy)]
+pub struct CountOptions {
+    threads: Option<usize>,
+    case: CaseSense,
+    #[cfg(feature = "unicode-normalization")]
+    normalization: Normalization,
+    filter: fn(char) -> bool,
+    expansion: Expansion,
+    sequential_merge: bool,
+}
+
+#[cfg(feature = "std")]
+impl Default for CountOptions {
+    fn default() -> Self {
+        CountOptions {
+            threads: None,
+            case: CaseSense::InsensitiveASCIIOnly,
+            #[cfg(feature = "unicode-normalization")]
+            normalization: Normalization::None,
+            filter: |_| true,
+            expansion: Expansion::Expand,
+            sequential_merge: false,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl CountOptions {
+    /// Sets how many threads count at the same time.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Sets the CaseSense applied to the characters.
+    pub fn case(mut self, case: CaseSense) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// Sets the Normalization applied to the text before counting.
+    #[cfg(feature = "unicode-normalization")]
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Sets which characters are counted.
+    pub fn filter(mut self, filter: fn(char) -> bool) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the Expansion applied to the characters whose case converts to several.
+    pub fn expansion(mut self, expansion: Expansion) -> Self {
+        self.expansion = expansion;
+        self
+    }
+
+    /// Sets whether the maps of the threads are merged only after all of them finished counting,
+    /// instead of each one as soon as its thread finishes.
+    pub fn sequential_merge(mut self, sequential_merge: bool) -> Self {
+        self.sequential_merge = sequential_merge;
+        self
+    }
+}
+
+/// Counts the frequencies of chars from a string with the given CountOptions.
+///
+/// The functions taking some of the options as parameters, like
+/// character_frequencies_with_n_threads_w_case(), remain as shortcuts counting the same,
+/// except that count() applies the Expansion where they panic.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = count("Hello, World!", &CountOptions::default());
+/// # assert_eq!(frequency_map, character_frequencies("Hello, World!"));
+/// ```
+#[cfg(feature = "std")]
+pub fn count(text: &str, options: &CountOptions) -> HashMap<char, usize> {
+    #[cfg(feature = "unicode-normalization")]
+    let text = &*normalize(text, options.normalization);
+    let threads = options.threads.unwrap_or_else(|| auto_threads(text));
+    let CountOptions {
+        case,
+        filter,
+        expansion,
+        sequential_merge,
+        ..
+    } = *options;
+    let count_chunk = |chunk: Range<usize>| {
+        let chars = text[chunk].chars().filter(|&ch| filter(ch));
+        Ok(count_chars_expanding(chars, case, expansion))
+    };
+    let frequency_map = if threads <= 1 {
+        count_chunk(0..text.len())
+    } else if sequential_merge {
+        let chunks = char_boundary_ranges(text, threads);
+        let partials: Result<Vec<CharMap>, FreqError> =
+            run_each(chunks, count_chunk).into_iter().collect();
+        partials.map(merge_into_largest)
+    } else {
+        try_count_merged(char_boundary_ranges(text, threads), count_chunk)
+    };
+    into_std_map(frequency_map.unwrap())
+}
+
+/// CountStats reports how the counting of a text was split.
+/// * threads - how many threads counted, 1 if the text was counted on the calling thread.
+/// * chunks - how many parts the text was split in.
+/// * chars - how many characters of the text were read, including the ones left out or
+///   collapsed, and counting once the ones whose case converts to several.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct CountStats {
+    pub threads: usize,
+    pub chunks: usize,
+    pub chars: usize,
+}
+
+/// Texts shorter than this many bytes are counted on the calling thread by the functions
+/// that choose the amount of threads, as starting threads takes longer than counting them.
+#[cfg(feature = "std")]
+pub const PARALLEL_THRESHOLD: usize = 1 << 15;
+
+// Amount of threads for counting the text when none is given.
+#[cfg(feature = "std")]
+fn auto_threads(text: &str) -> usize {
+    if text.len() < PARALLEL_THRESHOLD {
+        1
+    } else {
+        default_threads()
+    }
+}
+
+// Splits the text in ranges of chunk_chars characters.
+#[cfg(feature = "std")]
+fn char_chunks(text: &str, chunk_chars: usize) -> Vec<Range<usize>> {
+    let mut starts: Vec<usize> = text
+        .char_indices()
+        .step_by(chunk_chars)
+        .map(|(index, _)| index)
+        .collect();
+    starts.push(text.len());
+    starts.windows(2).map(|pair| pair[0]..pair[1]).collect()
+}
+
+// Counts the chunks of the text, the threads taking the next chunk as they finish.
+// With collapse_whitespace every run of whitespace is counted as a single ' ',
+// including runs continuing from one chunk into the next.
+#[cfg(feature = "std")]
+fn try_chunked_frequencies(
+    text: &str,
+    chunks: &[Range<usize>],
+    threads: usize,
+    case: CaseSense,
+    collapse_whitespace: bool,
+) -> Result<(CharMap, CountStats), FreqError> {
+    let next = AtomicUsize::new(0);
+    let counted = AtomicUsize::new(0);
+
+    let threads = min(max(1, threads), chunks.len());
+    let mut frequency_map = try_count_merged((0..threads).collect(), |_| {
+        let mut frequency_map = CharMap::default();
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let chunk = match chunks.get(index) {
+                Some(chunk) => &text[chunk.clone()],
+                None => return Ok(frequency_map),
+            };
+            let read = try_count_chunk_into(chunk, case, collapse_whitespace, &mut frequency_map)?;
+            counted.fetch_add(read, Ordering::Relaxed);
+        }
+    })?;
+
+    if collapse_whitespace {
+        uncount_split_whitespace(text, chunks, &mut frequency_map);
+    }
+    Ok(with_stats(
+        frequency_map,
+        threads,
+        chunks.len(),
+        counted.into_inner(),
+    ))
+}
+
+// Same as try_chunked_frequencies but counting each chunk into its own map, and merging them
+// in the order of the chunks after all are counted. Also returns the characters in the order
+// they first appear in the text.
+#[cfg(feature = "std")]
+fn try_ordered_chunked_frequencies(
+    text: &str,
+    chunks: &[Range<usize>],
+    threads: usize,
+    case: CaseSense,
+    collapse_whitespace: bool,
+) -> Result<(CharMap, Vec<char>, CountStats), FreqError> {
+    let next = AtomicUsize::new(0);
+    let counted = AtomicUsize::new(0);
+
+    let threads = min(max(1, threads), chunks.len());
+    let workers = run_each((0..threads).collect(), |_| {
+        let mut partials = Vec::new();
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let chunk = match chunks.get(index) {
+                Some(chunk) => &text[chunk.clone()],
+                None => return Ok(partials),
+            };
+            let mut frequency_map = OrderedCharMap::default();
+            let read = try_count_chunk_into(chunk, case, collapse_whitespace, &mut frequency_map)?;
+            counted.fetch_add(read, Ordering::Relaxed);
+            partials.push((index, frequency_map));
+        }
+    });
+    let mut partials = Vec::with_capacity(chunks.len());
+    for worker in workers {
+        partials.extend(worker?);
+    }
+    partials.sort_unstable_by_key(|&(index, _)| index);
+
+    let mut ordered = OrderedCharMap::default();
+    for (_, partial) in partials {
+        for character in partial.order {
+            ordered.add(character, partial.frequency_map[&character]);
+        }
+    }
+    if collapse_whitespace {
+        uncount_split_whitespace(text, chunks, &mut ordered.frequency_map);
+    }
+    let (frequency_map, stats) = with_stats(
+        ordered.frequency_map,
+        threads,
+        chunks.len(),
+        counted.into_inner(),
+    );
+    Ok((frequency_map, ordered.order, stats))
+}
+
+// A run of whitespace crossing the boundary between two chunks was counted once on each
+// side of it, so one of them is taken back.
+#[cfg(feature = "std")]
+fn uncount_split_whitespace(text: &str, chunks: &[Range<usize>], frequency_map: &mut CharMap) {
+    for boundary in chunks.iter().skip(1).map(|chunk| chunk.start) {
+        let before = text[..boundary].chars().next_back();
+        let after = text[boundary..].chars().next();
+        if before.is_some_and(char::is_whitespace) && after.is_some_and(char::is_whitespace) {
+            if let Some(frequency) = frequency_map.get_mut(&' ') {
+                *frequency -= 1;
+            }
+        }
+    }
+}
+
+// CountMap keeping the characters in the order they were first added.
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct OrderedCharMap {
+    frequency_map: CharMap,
+    order: Vec<char>,
+}
+
+#[cfg(feature = "std")]
+impl CountMap for OrderedCharMap {
+    fn add(&mut self, character: char, frequency: usize) {
+        let count = self.frequency_map.entry(character).or_insert_with(|| {
+            self.order.push(character);
+            0
+        });
+        *count += frequency;
+    }
+
+    fn len(&self) -> usize {
+        self.frequency_map.len()
+    }
+}
+
+#[cfg(feature = "std")]
+impl IntoIterator for OrderedCharMap {
+    type Item = (char, usize);
+    type IntoIter = std::collections::hash_map::IntoIter<char, usize>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.frequency_map.into_iter()
+    }
+}
+
+// Counts a chunk into acc for the chunked counts, returning how many chars it read, which
+// are counted in the same pass instead of decoding the chunk again.
+#[cfg(feature = "std")]
+fn try_count_chunk_into<M: CountMap>(
+    chunk: &str,
+    case: CaseSense,
+    collapse_whitespace: bool,
+    acc: &mut M,
+) -> Result<usize, FreqError> {
+    let mut read = 0;
+    let chars = chunk.chars().inspect(|_| read += 1);
+    if collapse_whitespace {
+        try_count_collapsed_into(chars, case, acc)?;
+    } else {
+        try_count_chars_into(chars, case, acc)?;
+    }
+    Ok(read)
+}
+
+// Same as try_count_chars_into but counting every run of whitespace as a single ' '.
+#[cfg(feature = "std")]
+fn try_count_collapsed_into<M: CountMap>(
+    chars: impl Iterator<Item = char>,
+    case: CaseSense,
+    acc: &mut M,
+) -> Result<(), FreqError> {
+    let mut in_whitespace = false;
+    for ch in chars {
+        if ch.is_whitespace() {
+            if !in_whitespace {
+                acc.add(' ', 1);
+            }
+            in_whitespace = true;
+        } else {
+            try_add_folded(acc, ch, case, 1)?;
+            in_whitespace = false;
+        }
+    }
+    Ok(())
+}
+
+/// Counts the frequencies of chars from several files and merges them into one map.
+///
+/// The files are distributed over as many threads as cpu's, each thread reading
+/// and counting whole files. Any I/O error, including a file not being valid
+/// UTF-8, is returned instead of the frequencies.
+///
+/// # Example
+/// ```no_run
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let frequency_map =
+///     character_frequencies_from_paths(&["a.txt", "b.txt"], CaseSense::Sensitive)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_from_paths<P: AsRef<Path>>(
+    paths: &[P],
+    case: CaseSense,
+) -> io::Result<HashMap<char, usize>> {
+    let paths: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect();
+    let next = AtomicUsize::new(0);
+
+    let workers = (0..min(default_threads(), paths.len())).collect();
+    let partials = run_each(workers, |_| -> io::Result<CharMap> {
+        let mut frequency_map = CharMap::default();
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let path = match paths.get(index) {
+                Some(path) => path,
+                None => return Ok(frequency_map),
+            };
+            let text = fs::read_to_string(path)?;
+            count_into(&text, case, &mut frequency_map);
+        }
+    });
+
+    let mut frequency_map = CharMap::default();
+    for partial in partials {
+        frequency_map = add_frequencies(frequency_map, partial?);
+    }
+    Ok(into_std_map(frequency_map))
+}
+
+/// Counts the frequencies of chars read from the reader until its end, with as many
+/// threads as cpu's.
+///
+/// Returns an error of kind io::ErrorKind::InvalidData if what is read is not valid UTF-8,
+/// or the error of the reader if reading fails.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let reader = std::io::Cursor::new("Hello, World!");
+/// let frequency_map = character_frequencies_from_reader(reader, CaseSense::Sensitive)?;
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_from_reader<R: Read>(
+    mut reader: R,
+    case: CaseSense,
+) -> io::Result<HashMap<char, usize>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    Ok(character_frequencies_w_case(text, case))
+}
+
+/// Counts the frequencies of chars read from the standard input until its end, with as
+/// many threads as cpu's, like character_frequencies_from_reader() on the locked stdin.
+///
+/// # Example
+/// ```no_run
+/// use character_frequency::*;
+/// let frequency_map = character_frequencies_from_stdin(CaseSense::Sensitive)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_from_stdin(case: CaseSense) -> io::Result<HashMap<char, usize>> {
+    character_frequencies_from_reader(io::stdin().lock(), case)
+}
+
+/// Counts the frequencies of chars from several strings as if they were a single one,
+/// without joining them.
+///
+/// Each thread counts a run of whole segments, so a segment is never split. When the
+/// segments are shorter than PARALLEL_THRESHOLD bytes in total they are counted on the
+/// calling thread.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map =
+///     character_frequencies_from_segments(&["Hello", ", ", "World"], CaseSense::Sensitive);
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_from_segments(
+    segments: &[&str],
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    let bytes: usize = segments.iter().map(|segment| segment.len()).sum();
+    let threads = if bytes < PARALLEL_THRESHOLD {
+        1
+    } else {
+        default_threads()
+    };
+    let per_thread = max(1, segments.len().div_ceil(threads));
+    let partials = run_each(segments.chunks(per_thread).collect(), |segments| {
+        let mut frequency_map = CharMap::default();
+        for segment in segments {
+            count_into(segment, case, &mut frequency_map);
+        }
+        frequency_map
+    });
+    into_std_map(merge_into_largest(partials))
+}
+
+/// Counts the frequencies of the chars of a slice with as many threads as cpu's, without
+/// collecting them into a string. The slice is split in one part of similar length per
+/// thread, or counted on the calling thread when shorter than PARALLEL_THRESHOLD bytes.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let chars: Vec<char> = "Hello, World!".chars().collect();
+/// let frequency_map = character_frequencies_from_slice(&chars, CaseSense::Sensitive);
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_from_slice(chars: &[char], case: CaseSense) -> HashMap<char, usize> {
+    let threads = if std::mem::size_of_val(chars) < PARALLEL_THRESHOLD {
+        1
+    } else {
+        default_threads()
+    };
+    let per_thread = max(1, chars.len().div_ceil(threads));
+    let frequency_map = try_count_merged(chars.chunks(per_thread).collect(), |chunk| {
+        try_count_chars(chunk.iter().copied(), case, SkipSet::NONE)
+    });
+    into_std_map(frequency_map.unwrap())
+}
+
+/// Counts the frequencies of chars of each line of a string separately, returning one map
+/// per line in the order of the lines.
+///
+/// The lines are split like str::lines(), so the "\n" or "\r\n" ending them is not
+/// counted and a final line ending doesn't start an empty line. Each thread counts a run
+/// of whole lines.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let lines = per_line_frequencies("aab\nbc\n", CaseSense::Sensitive);
+/// # assert_eq!(lines.len(), 2);
+/// # assert_eq!(lines[0][&'a'], 2);
+/// # assert_eq!(lines[1][&'c'], 1);
+/// ```
+#[cfg(feature = "std")]
+pub fn per_line_frequencies(text: &str, case: CaseSense) -> Vec<HashMap<char, usize>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let per_thread = max(1, lines.len().div_ceil(auto_threads(text)));
+    let partials = run_each(lines.chunks(per_thread).collect(), |lines| {
+        lines
+            .iter()
+            .map(|line| {
+                let mut frequency_map = HashMap::new();
+                count_into(line, case, &mut frequency_map);
+                frequency_map
+            })
+            .collect::<Vec<_>>()
+    });
+    partials.into_iter().flatten().collect()
+}
+
+/// Counts the frequencies of chars from a string on the threads of a rayon ThreadPool
+/// instead of spawning new ones, splitting it in one chunk per thread of the pool.
+///
+/// Requires the `rayon` feature.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+/// let frequency_map = character_frequencies_in_pool("Hello, World!", CaseSense::Sensitive, &pool);
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// ```
+#[cfg(feature = "rayon")]
+pub fn character_frequencies_in_pool(
+    text: &str,
+    case: CaseSense,
+    pool: &rayon::ThreadPool,
+) -> HashMap<char, usize> {
+    use rayon::prelude::*;
+
+    let chunks = char_boundary_ranges(text, pool.current_num_threads());
+    let partials: Result<Vec<CharMap>, FreqError> = pool.install(|| {
+        chunks
+            .into_par_iter()
+            .map(|chunk| try_count_chars(text[chunk].chars(), case, SkipSet::NONE))
+            .collect()
+    });
+    into_std_map(merge_into_largest(partials.unwrap()))
+}
+
+/// Counts the frequencies of chars from bytes in a legacy encoding, like Windows-1252,
+/// decoding them first.
+///
+/// Requires the `encoding_rs` feature. A byte order mark of the encoding at the start is
+/// not counted, and invalid sequences are counted as U+FFFD REPLACEMENT CHARACTER.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map =
+///     character_frequencies_encoded(b"caf\xe9", encoding_rs::WINDOWS_1252, CaseSense::Sensitive);
+/// # assert_eq!(frequency_map[&'é'], 1);
+/// ```
+#[cfg(feature = "encoding_rs")]
+pub fn character_frequencies_encoded(
+    data: &[u8],
+    encoding: &'static Encoding,
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    let (text, _) = encoding.decode_with_bom_removal(data);
+    character_frequencies_w_case(&text, case)
+}
+
+/// Counts the frequencies of chars from a file with as many threads as cpu's, reading
+/// it through a memory map instead of copying it into a String.
+///
+/// Requires the `memmap2` feature. The mapped bytes are split at char boundaries and
+/// each thread validates and counts its part. If the file is not valid UTF-8 an
+/// error of kind InvalidData is returned.
+///
+/// The file must not be modified while it is being counted.
+///
+/// # Example
+/// ```no_run
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let frequency_map = character_frequencies_mmap("big.txt", CaseSense::Sensitive)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[cfg(feature = "memmap2")]
+pub fn character_frequencies_mmap<P: AsRef<Path>>(
+    path: P,
+    case: CaseSense,
+) -> io::Result<HashMap<char, usize>> {
+    let file = fs::File::open(path)?;
+    if file.metadata()?.len() == 0 {
+        return Ok(HashMap::new());
+    }
+    // SAFETY: the documentation requires the file not to be modified while mapped.
+    let mapped = unsafe { memmap2::Mmap::map(&file)? };
+
+    let ranges = utf8_boundary_ranges(&mapped, default_threads());
+    let partials = run_each(ranges, |range| -> io::Result<CharMap> {
+        let text = std::str::from_utf8(&mapped[range])
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let mut frequency_map = CharMap::default();
+        count_into(text, case, &mut frequency_map);
+        Ok(frequency_map)
+    });
+
+    let mut frequency_map = CharMap::default();
+    for partial in partials {
+        frequency_map = add_frequencies(frequency_map, partial?);
+    }
+    Ok(into_std_map(frequency_map))
+}
+
+#[cfg(feature = "std")]
+pub fn sequential_character_frequencies(text: impl AsRef<str>) -> HashMap<char, usize> {
+    sequential_character_frequencies_w_case(text, CaseSense::InsensitiveASCIIOnly)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn sequential_character_frequencies(text: impl AsRef<str>) -> BTreeMap<char, usize> {
+    sequential_character_frequencies_w_case(text, CaseSense::InsensitiveASCIIOnly)
+}
+
+// Same as sequential_character_frequencies but with Case Sensitivity
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let frequency_map = sequential_character_frequencies_w_case("Hello, WORLD",CaseSense::Sensitive);
+/// ```
+#[cfg(feature = "std")]
+pub fn sequential_character_frequencies_w_case(
+    text: impl AsRef<str>,
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    try_sequential_character_frequencies_w_case(text, case).unwrap()
+}
+
+// Same as sequential_character_frequencies_w_case but counting into a BTreeMap, without std
+#[cfg(not(feature = "std"))]
+pub fn sequential_character_frequencies_w_case(
+    text: impl AsRef<str>,
+    case: CaseSense,
+) -> BTreeMap<char, usize> {
+    try_sequential_character_frequencies_w_case(text, case).unwrap()
+}
+
+/// Same as sequential_character_frequencies_w_case() but returning an error instead of panicking
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let result = try_sequential_character_frequencies_w_case("ß", CaseSense::InsensitiveUppercase);
+/// assert_eq!(result, Err(FreqError::MultiCharacterUppercase('ß')));
+/// ```
+#[cfg(feature = "std")]
+pub fn try_sequential_character_frequencies_w_case(
+    text: impl AsRef<str>,
+    case: CaseSense,
+) -> Result<HashMap<char, usize>, FreqError> {
+    let text = text.as_ref();
+    try_count_range(text, 0, text.len().saturating_sub(1), case, SkipSet::NONE).map(into_std_map)
+}
+
+// Same as try_sequential_character_frequencies_w_case but counting into a BTreeMap, without std
+#[cfg(not(feature = "std"))]
+pub fn try_sequential_character_frequencies_w_case(
+    text: impl AsRef<str>,
+    case: CaseSense,
+) -> Result<BTreeMap<char, usize>, FreqError> {
+    let text = text.as_ref();
+    try_count_range(text, 0, text.len().saturating_sub(1), case, SkipSet::NONE)
+}
+
+/// Counts the frequencies of chars from a string adding the weight of each character
+/// instead of 1 for every occurrence.
+///
+/// The weight is computed on the character after applying the CaseSense. A character whose
+/// case converts to several, like 'ß' case folded to "ss", adds the weight of each of them.
+/// Characters with a weight of 0 are left out of the map.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let frequency_map = weighted_character_frequencies("Hello", CaseSense::Sensitive, |ch| {
+///     if "aeiou".contains(ch) { 2 } else { 1 }
+/// });
+/// # assert_eq!(frequency_map[&'e'], 2);
+/// # assert_eq!(frequency_map[&'l'], 2);
+/// ```
+#[cfg(feature = "std")]
+pub fn weighted_character_frequencies(
+    text: &str,
+    case: CaseSense,
+    weight: impl Fn(char) -> usize,
+) -> HashMap<char, usize> {
+    let mut frequency_map = CharMap::default();
+    let mut add = |character: char| {
+        let weight = weight(character);
+        if weight > 0 {
+            *frequency_map.entry(character).or_insert(0) += weight;
+        }
+    };
+    for ch in text.chars() {
+        match fold_char(ch, case) {
+            Some(folded) => add(folded),
+            None => match case {
+                CaseSense::InsensitiveUppercase => ch.to_uppercase().for_each(&mut add),
+                #[cfg(feature = "caseless")]
+                CaseSense::CaseFold => core::iter::once(ch).default_case_fold().for_each(&mut add),
+                _ => ch.to_lowercase().for_each(&mut add),
+            },
+        }
+    }
+    into_std_map(frequency_map)
+}
+
+/// Amount of characters counted by character_frequencies_with_progress() between
+/// calls to the progress callback and checks of the cancel flag.
+#[cfg(feature = "std")]
+pub const PROGRESS_INTERVAL: usize = 4096;
+
+/// Counts the frequencies of chars from a string in the current thread, reporting
+/// progress and allowing the count to be cancelled.
+///
+/// Every PROGRESS_INTERVAL characters, and once more at the end, progress is called
+/// with the amount of characters counted so far. If the cancel flag is set at one
+/// of these points, counting stops and the frequencies counted so far are returned.
+/// The flag can be set from the progress callback itself or from another thread.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// use std::sync::atomic::AtomicBool;
+///
+/// let cancel = AtomicBool::new(false);
+/// let frequency_map = character_frequencies_with_progress(
+///     "Hello, World!",
+///     CaseSense::InsensitiveASCIIOnly,
+///     |processed| println!("{} characters counted", processed),
+///     &cancel,
+/// );
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_with_progress(
+    text: &str,
+    case: CaseSense,
+    mut progress: impl FnMut(usize),
+    cancel: &AtomicBool,
+) -> HashMap<char, usize> {
+    let mut frequency_map = CharMap::default();
+    let mut processed = 0;
+    for ch in text.chars() {
+        add_folded(&mut frequency_map, ch, case, 1);
+        processed += 1;
+        if processed % PROGRESS_INTERVAL == 0 {
+            progress(processed);
+            if cancel.load(Ordering::Relaxed) {
+                return into_std_map(frequency_map);
+            }
+        }
+    }
+    progress(processed);
+    into_std_map(frequency_map)
+}
+
+/// Adds the frequencies of chars from a string into an existing map.
+///
+/// Calling it repeatedly accumulates the counts of every text passed,
+/// without allocating a new map or merging results afterwards.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let mut frequency_map = HashMap::new();
+/// count_into("Hello", CaseSense::InsensitiveASCIIOnly, &mut frequency_map);
+/// count_into("World", CaseSense::InsensitiveASCIIOnly, &mut frequency_map);
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// ```
+pub fn count_into<M: CountMap>(text: &str, case: CaseSense, acc: &mut M) {
+    try_count_into(text, case, acc).unwrap()
+}
+
+/// Same as count_into() but returning an error instead of panicking.
+/// The characters before the one causing the error are already added to the map.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let mut frequency_map = HashMap::new();
+/// let result = try_count_into("aİ", CaseSense::Insensitive, &mut frequency_map);
+/// assert_eq!(result, Err(FreqError::MultiCharacterLowercase('İ')));
+/// # assert_eq!(frequency_map[&'a'], 1);
+/// ```
+pub fn try_count_into<M: CountMap>(
+    text: &str,
+    case: CaseSense,
+    acc: &mut M,
+) -> Result<(), FreqError> {
+    try_count_chars_into(text.chars(), case, acc)
+}
+
+// Same as try_count_into but counting the chars of any iterator.
+fn try_count_chars_into<M: CountMap>(
+    chars: impl Iterator<Item = char>,
+    case: CaseSense,
+    acc: &mut M,
+) -> Result<(), FreqError> {
+    for ch in chars {
+        try_add_folded(acc, ch, case, 1)?;
+    }
+    Ok(())
+}
+
+/// Same as character_frequencies_w_case() but applying the Expansion to the characters whose
+/// case converts to several, instead of panicking.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let case = CaseSense::InsensitiveUppercase;
+/// let frequency_map = character_frequencies_w_expansion("Straße", case, Expansion::Expand);
+/// # assert_eq!(frequency_map[&'S'], 3);
+/// let frequency_map = character_frequencies_w_expansion("Straße", case, Expansion::Skip);
+/// # assert_eq!(frequency_map[&'S'], 1);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_w_expansion(
+    text: &str,
+    case: CaseSense,
+    expansion: Expansion,
+) -> HashMap<char, usize> {
+    count(
+        text,
+        &CountOptions::default().case(case).expansion(expansion),
+    )
+}
+
+/// Counts the frequencies of the first max_chars chars of a string with as many threads
+/// as cpu's, or of all of them if it is shorter.
+///
+/// The prefix is cut at a char boundary, so a multibyte character is never split.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = character_frequencies_prefix("ὈΔΥΣΣΕΎΣ", 4, CaseSense::Sensitive);
+/// # assert_eq!(frequency_map.values().sum::<usize>(), 4);
+/// # assert_eq!(frequency_map[&'Σ'], 1);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_prefix(
+    text: &str,
+    max_chars: usize,
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    let end = text
+        .char_indices()
+        .nth(max_chars)
+        .map_or(text.len(), |(index, _)| index);
+    character_frequencies_w_case(&text[..end], case)
+}
+
+/// Counts the frequencies of a random sample of about sample_rate of the chars of a
+/// string, with as many threads as cpu's, for when rough proportions of a huge text are
+/// enough.
+///
+/// The counts are the raw counts of the sampled chars, not scaled, so they add up to
+/// about sample_rate times the chars of the text. Whether each char is sampled depends
+/// only on the seed and its position, so the same seed always samples the same chars.
+/// A sample_rate of 1.0 or more counts every char and one of 0.0 or less none of them.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let text = "abcd".repeat(10_000);
+/// let frequency_map = character_frequencies_sampled(&text, CaseSense::Sensitive, 0.1, 42);
+/// # assert_eq!(frequency_map, character_frequencies_sampled(&text, CaseSense::Sensitive, 0.1, 42));
+/// # assert!((800..1200).contains(&frequency_map[&'a']));
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_sampled(
+    text: &str,
+    case: CaseSense,
+    sample_rate: f64,
+    seed: u64,
+) -> HashMap<char, usize> {
+    let chunks = char_boundary_ranges(text, auto_threads(text));
+    let frequency_map = try_count_merged(chunks, |chunk| {
+        let start = chunk.start;
+        let sampled = text[chunk]
+            .char_indices()
+            .filter(|&(index, _)| is_sampled(seed, (start + index) as u64, sample_rate))
+            .map(|(_, ch)| ch);
+        try_count_chars(sampled, case, SkipSet::NONE)
+    });
+    into_std_map(frequency_map.unwrap())
+}
+
+// Whether the char at the byte offset is in the sample, hashing the seed and the offset
+// with splitmix64 into a number in [0, 1) compared with the sample rate.
+#[cfg(feature = "std")]
+fn is_sampled(seed: u64, offset: u64, sample_rate: f64) -> bool {
+    let mut z = seed.wrapping_add(offset.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    ((z >> 11) as f64 / (1u64 << 53) as f64) < sample_rate
+}
+
+/// Counts the frequencies of the chars of a string whose char index is in the range.
+///
+/// The range is of char indices, not byte indices, so it can't split a multibyte
+/// character. Like slicing, from..to includes from but not to. The part of the range
+/// that falls outside of the text is ignored.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let frequency_map = character_frequencies_range("ὈΔΥΣΣΕΎΣ", 2..5, CaseSense::Sensitive);
+/// # assert_eq!(frequency_map[&'Σ'], 2);
+/// # assert_eq!(frequency_map.len(), 2);
+/// ```
+#[cfg(feature = "std")]
+pub fn character_frequencies_range(
+    text: &str,
+    range: Range<usize>,
+    case: CaseSense,
+) -> HashMap<char, usize> {
+    try_character_frequencies_range(text, range, case).unwrap()
+}
+
+/// Same as character_frequencies_range() but returning an error instead of panicking
+#[cfg(feature = "std")]
+pub fn try_character_frequencies_range(
+    text: &str,
+    range: Range<usize>,
+    case: CaseSense,
+) -> Result<HashMap<char, usize>, FreqError> {
+    if range.is_empty() {
+        return Ok(HashMap::new());
+    }
+    try_count_range(text, range.start, range.end - 1, case, SkipSet::NONE).map(into_std_map)
+}
+
+fn try_count_range<M: CountMap>(
+    text: &str,
+    from: usize,
+    to: usize,
+    case_sense: CaseSense,
+    skip: SkipSet,
+) -> Result<M, FreqError> {
+    try_count_chars(
+        text.chars().skip(from).take(to - from + 1),
+        case_sense,
+        skip,
+    )
+}
+
+fn try_count_chars<M: CountMap>(
+    chars: impl Iterator<Item = char>,
+    case_sense: CaseSense,
+    skip: SkipSet,
+) -> Result<M, FreqError> {
+    let mut frequency_map = M::default();
+    for ch in chars.filter(|&ch| !skip.skips(ch)) {
+        try_add_folded(&mut frequency_map, ch, case_sense, 1)?;
+    }
+    Ok(frequency_map)
+}
+
+// Counts the characters applying the Expansion to the ones whose case converts to several.
+#[cfg(feature = "std")]
+fn count_chars_expanding<M: CountMap>(
+    chars: impl Iterator<Item = char>,
+    case_sense: CaseSense,
+    expansion: Expansion,
+) -> M {
+    let mut frequency_map = M::default();
+    for ch in chars {
+        let expanded: String = match try_add_folded(&mut frequency_map, ch, case_sense, 1) {
+            Ok(()) => continue,
+            Err(FreqError::MultiCharacterUppercase(_)) => ch.to_uppercase().collect(),
+            Err(_) => ch.to_lowercase().collect(),
+        };
+        match expansion {
+            Expansion::Expand => expanded.chars().for_each(|ch| frequency_map.add(ch, 1)),
+            Expansion::Skip => {}
+            Expansion::Replace(replacement) => frequency_map.add(replacement, 1),
+        }
+    }
+    frequency_map
+}
+
+// Adds frequency to the character with its case converted, or to each of the characters
+// it is case folded to.
+#[cfg(feature = "std")]
+fn add_folded<M: CountMap>(acc: &mut M, ch: char, case_sense: CaseSense, frequency: usize) {
+    try_add_folded(acc, ch, case_sense, frequency).unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn try_add_folded<M: CountMap>(
+    acc: &mut M,
+    ch: char,
+    case_sense: CaseSense,
+    frequency: usize,
+) -> Result<(), FreqError> {
+    #[cfg(feature = "caseless")]
+    if let CaseSense::CaseFold = case_sense {
+        for folded in core::iter::once(ch).default_case_fold() {
+            acc.add(folded, frequency);
+        }
+        return Ok(());
+    }
+    acc.add(try_fold_case(ch, case_sense)?, frequency);
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+fn fold_case(ch: char, case_sense: CaseSense) -> char {
+    try_fold_case(ch, case_sense).unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn try_fold_case(ch: char, case_sense: CaseSense) -> Result<char, FreqError> {
+    fold_char(ch, case_sense).ok_or(match case_sense {
+        CaseSense::InsensitiveUppercase => FreqError::MultiCharacterUppercase(ch),
+        #[cfg(feature = "caseless")]
+        CaseSense::CaseFold => FreqError::MultiCharacterCaseFold(ch),
+        _ => FreqError::MultiCharacterLowercase(ch),
+    })
+}
+
+/// Returns the character as it is counted with the CaseSense, or None if it can't be
+/// counted as a single character because its case converts to a multicharacter String.
+///
+/// Every counting function converts the characters with it, so it can be used to look up
+/// a character in their maps.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// assert_eq!(fold_char('Σ', CaseSense::Insensitive), Some('σ'));
+/// assert_eq!(fold_char('Σ', CaseSense::InsensitiveASCIIOnly), Some('Σ'));
+/// assert_eq!(fold_char('ß', CaseSense::InsensitiveUppercase), None);
+/// ```
+pub fn fold_char(ch: char, case: CaseSense) -> Option<char> {
+    match case {
+        CaseSense::Insensitive => single(ch.to_lowercase()),
+        CaseSense::InsensitiveASCIIOnly => Some(ch.to_ascii_lowercase()),
+        CaseSense::Sensitive => Some(ch),
+        CaseSense::InsensitiveLocale(Locale::Turkish | Locale::Azerbaijani) => match ch {
+            'I' => Some('ı'),
+            'İ' => Some('i'),
+            _ => fold_char(ch, CaseSense::Insensitive),
+        },
+        CaseSense::InsensitiveUppercase => single(ch.to_uppercase()),
+        CaseSense::InsensitiveASCIIOnlyUppercase => Some(ch.to_ascii_uppercase()),
+        #[cfg(feature = "caseless")]
+        CaseSense::CaseFold => single(core::iter::once(ch).default_case_fold()),
+    }
+}
+
+// Returns the only char of the iterator, or None if it has several.
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(ch),
+        _ => None,
+    }
+}
+
+/// Merges two frequency maps, adding up the frequencies of the chars present in both.
+/// The map with fewer characters is added into the other one, which is returned.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// # use std::collections::HashMap;
+/// let frequency_map = add_frequencies(
+///     character_frequencies("Hello"),
+///     character_frequencies("World"),
+/// );
+/// # assert_eq!(frequency_map[&'l'], 3);
+/// ```
+pub fn add_frequencies<M: CountMap>(a: M, b: M) -> M {
+    let (mut out, smaller) = if b.len() > a.len() { (b, a) } else { (a, b) };
+    for (character, frequency) in smaller {
+        out.add(character, frequency);
+    }
+    out
+}
+
+/// Removes the frequencies of remove from total, the inverse of add_frequencies().
+/// Frequencies saturate at 0 instead of underflowing, and the characters left with a
+/// frequency of 0 are dropped from the map.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = subtract_frequencies(
+///     character_frequencies("Hello World"),
+///     &character_frequencies("World"),
+/// );
+/// # assert_eq!(frequency_map[&'l'], 2);
+/// # assert!(!frequency_map.contains_key(&'w'));
+/// ```
+#[cfg(feature = "std")]
+pub fn subtract_frequencies(
+    total: HashMap<char, usize>,
+    remove: &HashMap<char, usize>,
+) -> HashMap<char, usize> {
+    let mut out = total;
+    for (character, &frequency) in remove {
+        if let Some(count) = out.get_mut(character) {
+            *count = count.saturating_sub(frequency);
+        }
+    }
+    out.retain(|_, &mut frequency| frequency > 0);
+    out
+}
+
+/// Drops the characters whose frequency is below min.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let frequency_map = retain_min(character_frequencies("aabc"), 2);
+/// # assert_eq!(frequency_map.len(), 1);
+/// # assert_eq!(frequency_map[&'a'], 2);
+/// ```
+#[cfg(feature = "std")]
+pub fn retain_min(freq: HashMap<char, usize>, min: usize) -> HashMap<char, usize> {
+    let mut out = freq;
+    out.retain(|_, &mut frequency| frequency >= min);
+    out
+}
+
+/// Returns the n characters with the lowest frequencies in ascending order of frequency.
+/// Characters with the same frequency are ordered by char, and if the map has fewer than n
+/// characters all of them are returned.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let rarest = bottom_n(&character_frequencies("aaabbc"), 2);
+/// # assert_eq!(rarest, vec![('c', 1), ('b', 2)]);
+/// ```
+#[cfg(feature = "std")]
+pub fn bottom_n(freq: &HashMap<char, usize>, n: usize) -> Vec<(char, usize)> {
+    let mut frequencies: Vec<(char, usize)> = freq.iter().map(|(&c, &f)| (c, f)).collect();
+    frequencies.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    frequencies.truncate(n);
+    frequencies
+}
+
+/// Counts the text with the given amount of threads and sequentially, panicking if the
+/// frequencies differ. The panic message lists every character counted differently, with
+/// its parallel and sequential frequencies.
+///
+/// Requires the `testutil` feature. Meant for testing and fuzzing the parallel counting
+/// on any input.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// assert_parallel_matches_sequential("Hello, Wörld! 日本語", 3, CaseSense::Insensitive);
+/// ```
+#[cfg(feature = "testutil")]
+pub fn assert_parallel_matches_sequential(text: &str, threads: usize, case: CaseSense) {
+    let parallel = character_frequencies_with_n_threads_w_case(text, threads, case);
+    let sequential = sequential_character_frequencies_w_case(text, case);
+
+    let characters: BTreeSet<char> = parallel.keys().chain(sequential.keys()).copied().collect();
+    let mismatches: Vec<String> = characters
+        .into_iter()
+        .filter_map(|character| {
+            let in_parallel = parallel.get(&character).copied().unwrap_or(0);
+            let in_sequential = sequential.get(&character).copied().unwrap_or(0);
+            (in_parallel != in_sequential).then(|| {
+                format!(
+                    "{:?}: parallel {}, sequential {}",
+                    character, in_parallel, in_sequential
+                )
+            })
+        })
+        .collect();
+    if !mismatches.is_empty() {
+        panic!(
+            "counting with {} threads differs from counting sequentially:\n{}",
+            threads,
+            mismatches.join("\n")
+        );
+    }
+}
+
+/// Returns the frequencies in descending order of frequency, with ties in ascending order
+/// of char, each with the fraction of the total frequency of it and all the ones before it.
+/// The last fraction is always 1.0. A map without any occurrence, empty or with every
+/// frequency 0, has no fractions and returns an empty Vec.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let distribution = cumulative_distribution(&character_frequencies("aaab"));
+/// # assert_eq!(distribution, vec![('a', 3, 0.75), ('b', 1, 1.0)]);
+/// ```
+#[cfg(feature = "std")]
+pub fn cumulative_distribution(freq: &HashMap<char, usize>) -> Vec<(char, usize, f64)> {
+    let total: usize = freq.values().sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    let mut cumulative = 0;
+    FrequencyMap::from(freq.clone())
+        .iter_ranked()
+        .map(|(character, frequency)| {
+            cumulative += frequency;
+            (character, frequency, cumulative as f64 / total as f64)
+        })
+        .collect()
+}
+
+/// Returns the share of the total frequency of each character as a percentage rounded to
+/// the given amount of decimals, in descending order of frequency with ties in ascending
+/// order of char.
+///
+/// Each percentage is rounded on its own, so they don't always add up to exactly 100.
+/// A map without any occurrence, empty or with every frequency 0, returns an empty Vec.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let shares = percentages(&character_frequencies("abc"), 2);
+/// # assert_eq!(shares, vec![('a', 33.33), ('b', 33.33), ('c', 33.33)]);
+/// ```
+#[cfg(feature = "std")]
+pub fn percentages(freq: &HashMap<char, usize>, decimals: usize) -> Vec<(char, f64)> {
+    let total: usize = freq.values().sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    let scale = 10f64.powi(min(decimals, i32::MAX as usize) as i32);
+    FrequencyMap::from(freq.clone())
+        .iter_ranked()
+        .map(|(character, frequency)| {
+            let percentage = frequency as f64 * 100.0 / total as f64;
+            // from 2^53 up every f64 is a whole number, so there is nothing left to round,
+            // and an overflowed scaled value would turn the percentage into infinity
+            let scaled = percentage * scale;
+            if scaled.abs() < (1u64 << f64::MANTISSA_DIGITS) as f64 {
+                (character, scaled.round() / scale)
+            } else {
+                (character, percentage)
+            }
+        })
+        .collect()
+}
+
+/// Returns the characters that appear exactly once, the hapax legomena, in ascending order.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let once = hapaxes(&character_frequencies("aabc"));
+/// # assert_eq!(once, vec!['b', 'c']);
+/// ```
+#[cfg(feature = "std")]
+pub fn hapaxes(freq: &HashMap<char, usize>) -> Vec<char> {
+    let mut characters: Vec<char> = freq
+        .iter()
+        .filter(|&(_, &frequency)| frequency == 1)
+        .map(|(&character, _)| character)
+        .collect();
+    characters.sort_unstable();
+    characters
+}
+
+/// Returns the cosine of the angle between the two maps as vectors with a frequency for
+/// each char, from 1.0 for maps with the same proportions of the same chars to 0.0 for
+/// maps without any char in common. If either map is empty, or all its frequencies are 0,
+/// returns 0.0.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let a = character_frequencies("Hello, World!");
+/// let b = character_frequencies("hello, world!".repeat(2));
+/// let similarity = cosine_similarity(&a, &b);
+/// # assert!((similarity - 1.0).abs() < 1e-9);
+/// ```
+#[cfg(feature = "std")]
+pub fn cosine_similarity(a: &HashMap<char, usize>, b: &HashMap<char, usize>) -> f64 {
+    let norm = |freq: &HashMap<char, usize>| {
+        freq.values()
+            .map(|&frequency| (frequency as f64).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    };
+    let (norm_a, norm_b) = (norm(a), norm(b));
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    let (smaller, larger) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let dot: f64 = smaller
+        .iter()
+        .filter_map(|(character, &frequency)| {
+            let other = larger.get(character)?;
+            Some(frequency as f64 * *other as f64)
+        })
+        .sum();
+    (dot / (norm_a * norm_b)).min(1.0)
+}
+
+/// Returns the frequencies in descending order of frequency, with ties in ascending order
+/// of char, each with the Unicode name of the char, like "LATIN SMALL LETTER A".
+/// The chars without a name, like the control characters, get their code point, like "U+0009".
+///
+/// Requires the `unicode-names2` feature.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let annotations = annotated(&character_frequencies("aa!"));
+/// # assert_eq!(annotations, vec![
+/// #     ('a', 2, "LATIN SMALL LETTER A".to_string()),
+/// #     ('!', 1, "EXCLAMATION MARK".to_string()),
+/// # ]);
+/// ```
+#[cfg(feature = "unicode-names2")]
+pub fn annotated(freq: &HashMap<char, usize>) -> Vec<(char, usize, String)> {
+    FrequencyMap::from(freq.clone())
+        .iter_ranked()
+        .map(|(character, frequency)| {
+            let name = match unicode_names2::name(character) {
+                Some(name) => name.to_string(),
+                None => format!("U+{:04X}", character as u32),
+            };
+            (character, frequency, name)
+        })
+        .collect()
+}
+
+/// Returns the frequencies as a JSON array of `{"char":"a","count":4}` objects, in descending
+/// order of frequency with ties in ascending order of char.
+///
+/// Quotes, backslashes and control characters are escaped, so the output is always valid JSON.
+///
+/// # Example
+/// ```
+/// use character_frequency::*;
+/// let json = to_json(&character_frequencies("aab"));
+/// # assert_eq!(json, r#"[{"char":"a","count":2},{"char":"b","count":1}]"#);
+/// ```
+#[cfg(feature = "std")]
+pub fn to_json(freq: &HashMap<char, usize>) -> String {
+    let objects: Vec<String> = FrequencyMap::from(freq.clone())
+        .iter_ranked()
+        .map(|(character, frequency)| {
+            format!(
+                "{{\"char\":\"{}\",\"count\":{}}}",
+                json_escape(character),
+                frequency
+            )
+        })
+        .collect();
+    format!("[{}]", objects.join(","))
+}
+
+// Escapes the character for a JSON string.
+#[cfg(feature = "std")]
+fn json_escape(ch: char) -> String {
+    match ch {
+        '"' => "\\\"".to_string(),
+        '\\' => "\\\\".to_string(),
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        '\u{8}' => "\\b".to_string(),
+        '\u{c}' => "\\f".to_string(),
+        _ if ch.is_control() => format!("\\u{:04x}", ch as u32),
+        _ => ch.to_string(),
+    }
+}
+
+/// Environment variable that overrides the amount of threads used by the functions counting
+/// with as many threads as cpu's. Values below 1 are counted as 1.
+#[cfg(feature = "std")]
+pub const THREADS_ENV_VAR: &str = "CHAR_FREQ_THREADS";
+
+// Amount of threads used when none is given, as many as cpu's unless the environment says otherwise.
+#[cfg(all(
+    feature = "std",
+    not(any(
+        feature = "single-thread",
+        all(target_family = "wasm", not(target_feature = "atomics"))
+    ))
+))]
+fn default_threads() -> usize {
+    threads_from_env(std::env::var(THREADS_ENV_VAR).ok().as_deref())
+}
+
+// Amount of threads for the value of THREADS_ENV_VAR, as many as cpu's if it is unset or
+// not a number.
+#[cfg(all(
+    feature = "std",
+    not(any(
+        feature = "single-thread",
+        all(target_family = "wasm", not(target_feature = "atomics"))
+    ))
+))]
+fn threads_from_env(value: Option<&str>) -> usize {
+    match value.and_then(|threads| threads.trim().parse().ok()) {
+        Some(threads) => max(1, threads),
+        None => thread::available_parallelism().map(Into::into).unwrap_or(1),
+    }
+}
+
+// Without threads everything is counted on the calling thread.
+#[cfg(all(
+    feature = "std",
+    any(
+        feature = "single-thread",
+        all(target_family = "wasm", not(target_feature = "atomics"))
+    )
+))]
+fn default_threads() -> usize {
+    1
+}
+
+// Converts an internal map into the standard HashMap returned by the public functions.
+#[cfg(feature = "std")]
+fn into_std_map(frequency_map: CharMap) -> HashMap<char, usize> {
+    #[cfg(feature = "fxhash")]
+    return frequency_map.into_iter().collect();
+    #[cfg(not(feature = "fxhash"))]
+    return frequency_map;
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential_character_frequencies_no_std() {
+        let result = sequential_character_frequencies("aaAbΣσ");
+        let expected = BTreeMap::from([('a', 3), ('b', 1), ('Σ', 1), ('σ', 1)]);
+        assert_eq!(result, expected);
+        let result = sequential_character_frequencies_w_case("aaAbΣσ", CaseSense::Insensitive);
+        let expected = BTreeMap::from([('a', 3), ('b', 1), ('σ', 2)]);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_character_frequencies_sorted_no_std() {
+        let result = character_frequencies_sorted("cbaA", CaseSense::Sensitive);
+        let keys: alloc::vec::Vec<char> = result.keys().copied().collect();
+        assert_eq!(keys, ['A', 'a', 'b', 'c']);
+    }
+
+    #[test]
+    fn test_count_into_and_add_frequencies_no_std() {
+        let mut result = BTreeMap::new();
+        count_into("ab", CaseSense::Sensitive, &mut result);
+        count_into("bc", CaseSense::Sensitive, &mut result);
+        let result = add_frequencies(result, sequential_character_frequencies("cd"));
+        let expected = BTreeMap::from([('a', 1), ('b', 2), ('c', 2), ('d', 1)]);
+        assert_eq!(result, expected);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    // convenience function for testing; simplifies giving expected frequencies.
+    // given "a4 b3 c2 d1 e1", return hashmap {a:4, b:3, c:2, d;1, e:1}
+    #[allow(clippy::from_str_radix_10)]
+    fn expected_freq(s: &str) -> HashMap<char, usize> {
+        HashMap::<char, usize>::from_iter(s.split(" ").map(|chunk| {
+            (
+                chunk.chars().next().unwrap(),
+                usize::from_str_radix(&chunk.chars().skip(1).collect::<String>(), 10).unwrap(),
+            )
+        }))
+    }
+
+    // counts the chars from index from to index to, both included.
+    fn count_range(text: &str, from: usize, to: usize, case: CaseSense) -> HashMap<char, usize> {
+        try_count_range(text, from, to, case, SkipSet::NONE).unwrap()
+    }
+
+    // writes each text into its own file in the temp dir, the caller must remove them.
+    fn temp_files(name: &str, texts: &[&str]) -> Vec<PathBuf> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let path = std::env::temp_dir().join(format!(
+                    "character_frequency_{}_{}_{}.txt",
+                    name,
+                    std::process::id(),
+                    index
+                ));
+                fs::write(&path, text).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    // The message of a panic caught by catch_unwind.
+    fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+        match panic.downcast::<String>() {
+            Ok(message) => *message,
+            Err(panic) => panic.downcast::<&str>().unwrap().to_string(),
+        }
+    }
+
+    // counts the text case sensitively into a new map with count_into.
+    fn count_into_new(text: &str) -> CharMap {
+        let mut frequency_map = CharMap::default();
+        count_into(text, CaseSense::Sensitive, &mut frequency_map);
+        frequency_map
+    }
+
+    // xorshift64, so the random texts are the same on every run.
+    #[cfg(feature = "testutil")]
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    #[test]
+    fn test_character_frequencies_range_full() {
+        let result = count_range("aaaabbbccd|@", 0, 11, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_consecutive_left() {
+        let result = count_range("aaaa", 0, 2, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a3"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_consecutive_right() {
+        let result = count_range("aaaa", 1, 3, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a3"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_consecutive_center() {
+        let result = count_range("aaaa", 1, 2, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a2"));
+        let result = count_range("baab", 1, 2, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a2"));
+        let result = count_range("bacb", 1, 2, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a1 c1"));
+        let result = count_range("dcab", 1, 2, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a1 c1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_consecutive_whole() {
+        let result = count_range("aaaa", 0, 3, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a4"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_only_one_left() {
+        let result = count_range("aaa", 0, 0, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_only_one_right() {
+        let result = count_range("aaa", 2, 2, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_only_one_center() {
+        let result = count_range("aaa", 1, 1, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(result, expected_freq("a1"));
+    }
+
+    #[test]
+    fn test_sequential_character_frequencies() {
+        let result = character_frequencies("aaaabbbccd|@");
+        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_more_threads_than_characters() {
+        let result = character_frequencies_with_n_threads("aaaabbbccd|@", 13);
+        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_less_threads_than_characters() {
+        let result = character_frequencies_with_n_threads("aaaabbbccd|@", 5);
+        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_single_thread() {
+        let result = character_frequencies_with_n_threads("aaaabbbccd|@", 1);
+        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_prime_threads() {
+        let result = character_frequencies_with_n_threads("aaaabbbccd|@", 7);
+        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_n_threads() {
+        let result = character_frequencies("aaaabbbccd|@");
+        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_full_w_case() {
+        let result = count_range("AaaaBbBCCd|@", 0, 11, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a3 b1 C2 d1 |1 @1 A1 B2"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_consecutive_left_w_case() {
+        let result = count_range("aaaA", 0, 2, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a3"));
+        let result = count_range("Aaaa", 0, 2, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a2 A1"));
+        let result = count_range("AaAa", 0, 2, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a1 A2"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_consecutive_right_w_case() {
+        let result = count_range("Aaaa", 1, 3, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a3"));
+        let result = count_range("AaAa", 1, 3, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a2 A1"));
+        let result = count_range("AaaA", 1, 3, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a2 A1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_consecutive_center_w_case() {
+        let result = count_range("aaaa", 1, 2, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a2"));
+        let result = count_range("baAb", 1, 2, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a1 A1"));
+        let result = count_range("bAcb", 1, 2, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("A1 c1"));
+        let result = count_range("dcab", 1, 2, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a1 c1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_consecutive_whole_w_case() {
+        let result = count_range("aaaa", 0, 3, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a4"));
+        let result = count_range("aAaa", 0, 3, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("A1 a3"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_only_one_left_w_case() {
+        let result = count_range("aaa", 0, 0, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a1"));
+        let result = count_range("AaA", 0, 0, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("A1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_only_one_right_w_case() {
+        let result = count_range("aaa", 2, 2, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a1"));
+        let result = count_range("BaA", 2, 2, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("A1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_range_only_one_center_w_case() {
+        let result = count_range("aaa", 1, 1, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a1"));
+        let result = count_range("aAa", 1, 1, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("A1"));
+    }
+
+    #[test]
+    fn test_sequential_character_frequencies_w_case() {
+        let result = character_frequencies_w_case("AaabbbccdEEE|@", CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("A1 a2 b3 c2 d1 |1 @1 E3"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_more_threads_than_characters_w_case() {
+        let result =
+            character_frequencies_with_n_threads_w_case("AabbbccdE|@", 13, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("A1 a1 b3 c2 d1 |1 @1 E1"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_less_threads_than_characters_w_case() {
+        let result =
+            character_frequencies_with_n_threads_w_case("AaaabbbccdEEE|@", 5, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("A1 a3 b3 c2 d1 |1 @1 E3"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_single_thread_w_case() {
+        let result =
+            character_frequencies_with_n_threads_w_case("aaaAbbBccd|@", 1, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a3 b2 B1 A1 c2 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_prime_threads_w_case() {
+        let result =
+            character_frequencies_with_n_threads_w_case("AaaBbbCcd|@", 7, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a2 A1 B1 b2 c1 C1 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_parallel_character_frequencies_n_threads_w_case() {
+        let result = character_frequencies_w_case("AaaBbbCcd|@", CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a2 A1 B1 b2 c1 C1 d1 |1 @1"));
+    }
+
+    #[test]
+    fn test_unicode_case_sensitive() {
+        let greek_upper = "ὈΔΥΣΣΕΎΣ";
+        let greek_lower = "ὀδυσσεύς";
+        let greek_mix = "ὀδυσσεύςὈΔΥΣΣΕΎΣ";
+        let resultu = character_frequencies_w_case(greek_upper, CaseSense::Sensitive);
+        let resultl = character_frequencies_w_case(greek_lower, CaseSense::Sensitive);
+        let resultm = character_frequencies_w_case(greek_mix, CaseSense::Sensitive);
+        assert_eq!(resultu, expected_freq("Ὀ1 Δ1 Υ1 Σ3 Ε1 Ύ1"));
+        assert_eq!(resultl, expected_freq("ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1"));
+        assert_eq!(
+            resultm,
+            expected_freq("Ὀ1 Δ1 Υ1 Σ3 Ε1 Ύ1 ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1")
+        );
+    }
+
+    #[test]
+    fn test_unicode_case_insensitiveasciionly() {
+        let greek_upper = "ὈΔΥΣΣΕΎΣ";
+        let greek_lower = "ὀδυσσεύς";
+        let greek_mix = "ὀδυσσεύςὈΔΥΣΣΕΎΣ";
+        let resultu = character_frequencies_w_case(greek_upper, CaseSense::InsensitiveASCIIOnly);
+        let resultl = character_frequencies_w_case(greek_lower, CaseSense::InsensitiveASCIIOnly);
+        let resultm = character_frequencies_w_case(greek_mix, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(resultu, expected_freq("Ὀ1 Δ1 Υ1 Σ3 Ε1 Ύ1"));
+        assert_eq!(resultl, expected_freq("ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1"));
+        assert_eq!(
+            resultm,
+            expected_freq("Ὀ1 Δ1 Υ1 Σ3 Ε1 Ύ1 ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1")
+        );
+    }
+
+    #[test]
+    fn test_unicode_case_insensitive() {
+        let greek_upper = "ὈΔΥΣΣΕΎΣ";
+        let greek_lower = "ὀδυσσεύς";
+        let greek_mix = "ὀδυσσεύςὈΔΥΣΣΕΎΣ";
+        let resultu = character_frequencies_w_case(greek_upper, CaseSense::Insensitive);
+        let resultl = character_frequencies_w_case(greek_lower, CaseSense::Insensitive);
+        let resultm = character_frequencies_w_case(greek_mix, CaseSense::Insensitive);
+        assert_eq!(resultu, expected_freq("ὀ1 δ1 υ1 σ3 ε1 ύ1"));
+        assert_eq!(resultl, expected_freq("ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1"));
+        assert_eq!(resultm, expected_freq("ὀ2 δ2 υ2 σ5 ε2 ς1 ύ2"));
+    }
+
+    #[test]
+    fn test_unicode_case_irrelevant() {
+        let chinese = "夫物芸芸，各復歸其根，歸根曰靜";
+        let expect = expected_freq("夫1 物1 芸2 ，2 各1 復1 其1 歸2 根2 曰1 靜1");
+        let resultc_s = character_frequencies_w_case(chinese, CaseSense::Sensitive);
+        let resultc_ia = character_frequencies_w_case(chinese, CaseSense::Insensitive);
+        let resultc_i = character_frequencies_w_case(chinese, CaseSense::InsensitiveASCIIOnly);
+        assert_eq!(resultc_s, expect);
+        assert_eq!(resultc_ia, expect);
+        assert_eq!(resultc_i, expect);
+    }
+
+    #[test]
+    fn test_count_ascii_lowercase() {
+        let mut expected = [0; 26];
+        expected[4] = 1;
+        expected[7] = 1;
+        expected[11] = 2;
+        expected[14] = 1;
+        assert_eq!(count_ascii_lowercase("Hello"), expected);
+        assert_eq!(count_ascii_lowercase("HeLLo, Wörld 123!")[11], 3);
+        assert_eq!(count_ascii_lowercase("ὈΔΥΣΣΕΎΣ 日本"), [0; 26]);
+        assert_eq!(count_ascii_lowercase(""), [0; 26]);
+
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(PARALLEL_THRESHOLD);
+        let letters = count_ascii_lowercase(&text);
+        let frequency_map = character_frequencies(&text);
+        for (letter, frequency) in ('a'..='z').zip(letters) {
+            assert_eq!(frequency, frequency_map[&letter]);
+        }
+    }
+
+    #[test]
+    fn test_character_frequencies_from_slice() {
+        let case = CaseSense::Sensitive;
+        let frequency_map = character_frequencies_from_slice(&['a', 'a', 'b'], case);
+        assert_eq!(frequency_map, character_frequencies_w_case("aab", case));
+        assert_eq!(character_frequencies_from_slice(&[], case), HashMap::new());
+
+        let text = "ὈΔΥΣΣΕΎΣ ὀδυσσεύς Hello".repeat(PARALLEL_THRESHOLD / 8);
+        let chars: Vec<char> = text.chars().collect();
+        for case in [CaseSense::Sensitive, CaseSense::Insensitive] {
+            let expected = character_frequencies_w_case(&text, case);
+            assert_eq!(character_frequencies_from_slice(&chars, case), expected);
+        }
+    }
+
+    #[test]
+    fn test_per_line_frequencies() {
+        let text = "Hello\nWorld!\r\n\nΣσς";
+        let expected = vec![
+            expected_freq("H1 e1 l2 o1"),
+            expected_freq("W1 o1 r1 l1 d1 !1"),
+            HashMap::new(),
+            expected_freq("Σ1 σ1 ς1"),
+        ];
+        assert_eq!(per_line_frequencies(text, CaseSense::Sensitive), expected);
+        let lines = per_line_frequencies("aA\nb\nAb\n", CaseSense::Insensitive);
+        assert_eq!(
+            lines,
+            vec![
+                expected_freq("a2"),
+                expected_freq("b1"),
+                expected_freq("a1 b1")
+            ]
+        );
+        assert_eq!(per_line_frequencies("", CaseSense::Sensitive), vec![]);
+
+        let text = "line one\nline two\n".repeat(PARALLEL_THRESHOLD / 8);
+        let lines = per_line_frequencies(&text, CaseSense::Sensitive);
+        assert_eq!(lines.len(), PARALLEL_THRESHOLD / 8 * 2);
+        assert_eq!(
+            lines[1],
+            character_frequencies_w_case("line two", CaseSense::Sensitive)
+        );
+    }
+
+    #[test]
+    fn test_text_as_ref() {
+        use std::borrow::Cow;
+        let expected = expected_freq("h1 e1 l3 o2 ,1 w1 r1 d1");
+        let owned = String::from("Hello,World");
+        let borrowed: Cow<str> = Cow::Borrowed("Hello,World");
+        let boxed: Box<str> = "Hello,World".into();
+        assert_eq!(character_frequencies("Hello,World"), expected);
+        assert_eq!(character_frequencies(&owned), expected);
+        assert_eq!(character_frequencies(owned), expected);
+        assert_eq!(character_frequencies(borrowed.clone()), expected);
+        assert_eq!(
+            character_frequencies(Cow::<str>::Owned("Hello,World".into())),
+            expected
+        );
+        assert_eq!(character_frequencies(&boxed), expected);
+        let case = CaseSense::InsensitiveASCIIOnly;
+        assert_eq!(character_frequencies_w_case(&borrowed, case), expected);
+        assert_eq!(
+            character_frequencies_with_n_threads_w_case(boxed, 3, case),
+            expected
+        );
+        assert_eq!(
+            sequential_character_frequencies_w_case(borrowed, case),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_public_character_frequencies_range_start() {
+        let result = character_frequencies_range("aabbcc", 0..3, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a2 b1"));
+    }
+
+    #[test]
+    fn test_public_character_frequencies_range_middle() {
+        let result = character_frequencies_range("aabbcc", 2..4, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("b2"));
+        let result = character_frequencies_range("ὀδυσσεύς", 3..6, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("σ2 ε1"));
+    }
+
+    #[test]
+    fn test_public_character_frequencies_range_end() {
+        let result = character_frequencies_range("aabbcc", 3..6, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("b1 c2"));
+        let result = character_frequencies_range("aabbcc", 4..100, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("c2"));
+    }
+
+    #[test]
+    fn test_public_character_frequencies_range_half_open() {
+        let text = "Hello, Wörld! ὈΔΥΣΣΕΎΣ";
+        let len = text.chars().count();
+        assert_eq!(
+            character_frequencies_range(text, 0..len, CaseSense::Sensitive),
+            character_frequencies_w_case(text, CaseSense::Sensitive)
+        );
+        let result = character_frequencies_range(text, 2..5, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("l2 o1"));
+        let result = character_frequencies_range(text, 5..5, CaseSense::Sensitive);
+        assert_eq!(result, HashMap::new());
+    }
+
+    #[test]
+    fn test_public_character_frequencies_range_out_of_bounds() {
+        let result = character_frequencies_range("aabbcc", 6..10, CaseSense::Sensitive);
+        assert_eq!(result, HashMap::new());
+        let result = character_frequencies_range("aabbcc", 3..3, CaseSense::Sensitive);
+        assert_eq!(result, HashMap::new());
+        let result = character_frequencies_range("", 0..1, CaseSense::Sensitive);
+        assert_eq!(result, HashMap::new());
+    }
+
+    #[test]
+    fn test_character_frequencies_prefix() {
+        let result = character_frequencies_prefix("aabbcc", 3, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a2 b1"));
+        let result = character_frequencies_prefix("日本語日本語", 4, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("日2 本1 語1"));
+        let result = character_frequencies_prefix("abc", 10, CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a1 b1 c1"));
+        let result = character_frequencies_prefix("abc", 0, CaseSense::Sensitive);
+        assert_eq!(result, HashMap::new());
+
+        let text = "ab€".repeat(PARALLEL_THRESHOLD);
+        let result =
+            character_frequencies_prefix(&text, 3 * PARALLEL_THRESHOLD / 2, CaseSense::Sensitive);
+        assert_eq!(result.values().sum::<usize>(), 3 * PARALLEL_THRESHOLD / 2);
+        assert_eq!(result[&'a'], PARALLEL_THRESHOLD / 2);
+    }
+
+    #[test]
+    fn test_count_into_accumulates() {
+        let mut result = HashMap::new();
+        count_into("aab", CaseSense::InsensitiveASCIIOnly, &mut result);
+        count_into("Bc", CaseSense::InsensitiveASCIIOnly, &mut result);
+        count_into("cCa|", CaseSense::InsensitiveASCIIOnly, &mut result);
+        assert_eq!(result, expected_freq("a3 b2 c3 |1"));
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    fn test_normalization_nfc_composed_and_decomposed() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        let case = CaseSense::InsensitiveASCIIOnly;
+        let resultc = character_frequencies_normalized(composed, case, Normalization::Nfc);
+        let resultd = character_frequencies_normalized(decomposed, case, Normalization::Nfc);
+        assert_eq!(resultc, expected_freq("c1 a1 f1 é1"));
+        assert_eq!(resultd, resultc);
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    fn test_normalization_none_and_nfd() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        let case = CaseSense::InsensitiveASCIIOnly;
+        let resultc = character_frequencies_normalized(composed, case, Normalization::None);
+        let resultd = character_frequencies_normalized(decomposed, case, Normalization::None);
+        assert_ne!(resultc, resultd);
+        let resultc = character_frequencies_normalized(composed, case, Normalization::Nfd);
+        assert_eq!(resultc, resultd);
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-segmentation")]
+    fn test_base_character_frequencies() {
+        let result = base_character_frequencies("cafe\u{301}", CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("c1 a1 f1 e1"));
+        let result = base_character_frequencies("E\u{301}e\u{300}\u{302}", CaseSense::Insensitive);
+        assert_eq!(result, expected_freq("e2"));
+        assert_eq!(
+            base_character_frequencies("", CaseSense::Sensitive),
+            HashMap::new()
+        );
+    }
+
+    #[test]
+    fn test_character_frequencies_from_paths() {
+        let paths = temp_files("from_paths", &["aaab", "", "bcC", "Σσ|@"]);
+        let result = character_frequencies_from_paths(&paths, CaseSense::InsensitiveASCIIOnly);
+        paths.iter().for_each(|path| fs::remove_file(path).unwrap());
+        assert_eq!(result.unwrap(), expected_freq("a3 b2 c2 Σ1 σ1 |1 @1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_from_paths_no_paths() {
+        let paths: [&str; 0] = [];
+        let result = character_frequencies_from_paths(&paths, CaseSense::Sensitive);
+        assert_eq!(result.unwrap(), HashMap::new());
+    }
+
+    #[test]
+    fn test_character_frequencies_from_paths_missing_file() {
+        let mut paths = temp_files("from_paths_missing", &["abc"]);
+        paths.push(std::env::temp_dir().join("character_frequency_does_not_exist.txt"));
+        let result = character_frequencies_from_paths(&paths, CaseSense::Sensitive);
+        fs::remove_file(&paths[0]).unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_character_frequencies_from_reader() {
+        let stdin = io::Cursor::new("aaab\nbcC Σσ");
+        let result = character_frequencies_from_reader(stdin, CaseSense::InsensitiveASCIIOnly);
+        let expected = character_frequencies("aaab\nbcC Σσ");
+        assert_eq!(result.unwrap(), expected);
+
+        let stdin = io::Cursor::new(Vec::new());
+        let result = character_frequencies_from_reader(stdin, CaseSense::Sensitive);
+        assert_eq!(result.unwrap(), HashMap::new());
+
+        let stdin = io::Cursor::new(vec![b'a', 0xff, b'b']);
+        let result = character_frequencies_from_reader(stdin, CaseSense::Sensitive);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    fn test_normalization_strip_diacritics() {
+        let case = CaseSense::InsensitiveASCIIOnly;
+        let result =
+            character_frequencies_normalized("résumé", case, Normalization::StripDiacritics);
+        assert_eq!(result, character_frequencies_w_case("resume", case));
+        let result = character_frequencies_normalized("áàâÁ", case, Normalization::StripDiacritics);
+        assert_eq!(result, expected_freq("a4"));
+        let result = character_frequencies_normalized("ńά", case, Normalization::StripDiacritics);
+        assert_eq!(result, expected_freq("n1 ά1"));
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    fn test_normalization_strip_all_diacritics() {
+        let case = CaseSense::InsensitiveASCIIOnly;
+        let strip_all = Normalization::StripAllDiacritics;
+        let result = character_frequencies_normalized("résumé", case, strip_all);
+        assert_eq!(result, character_frequencies_w_case("resume", case));
+        let result = character_frequencies_normalized("ńάα\u{301}", case, strip_all);
+        assert_eq!(result, expected_freq("n1 α2"));
+    }
+
+    #[test]
+    fn test_character_frequencies_from_segments() {
+        let result =
+            character_frequencies_from_segments(&["foo", "bar", "baz"], CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("f1 o2 b2 a2 r1 z1"));
+        assert_eq!(
+            result,
+            character_frequencies_w_case("foobarbaz", CaseSense::Sensitive)
+        );
+
+        let segments: Vec<String> = (0..1000).map(|i| format!("Ab{}€\n", i % 7)).collect();
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        let segments = segments.repeat(PARALLEL_THRESHOLD / 1000);
+        let case = CaseSense::InsensitiveASCIIOnly;
+        assert_eq!(
+            character_frequencies_from_segments(&segments, case),
+            character_frequencies_w_case(segments.concat(), case)
+        );
+        assert_eq!(
+            character_frequencies_from_segments(&[], case),
+            HashMap::new()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_character_frequencies_in_pool() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        let text = "Hello, Wörld! ¿Qué tal? 日本語\n".repeat(100);
+        for case in [CaseSense::Sensitive, CaseSense::Insensitive] {
+            assert_eq!(
+                character_frequencies_in_pool(&text, case, &pool),
+                sequential_character_frequencies_w_case(&text, case)
+            );
+        }
+        assert_eq!(
+            character_frequencies_in_pool("", CaseSense::Sensitive, &pool),
+            HashMap::new()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "encoding_rs")]
+    fn test_character_frequencies_encoded() {
+        let case = CaseSense::InsensitiveASCIIOnly;
+        let result = character_frequencies_encoded(b"Caf\xe9\xe9", encoding_rs::WINDOWS_1252, case);
+        assert_eq!(result, expected_freq("c1 a1 f1 é2"));
+        let result = character_frequencies_encoded(b"\xef\xbb\xbfa\xff", encoding_rs::UTF_8, case);
+        assert_eq!(result, expected_freq("a1 \u{fffd}1"));
+        let result = character_frequencies_encoded(b"\xe9", encoding_rs::ISO_8859_2, case);
+        assert_eq!(result, expected_freq("é1"));
+    }
+
+    #[test]
+    fn test_character_frequencies_with_total() {
+        let (result, total) = character_frequencies_with_total("AaaBbΣσ", CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("A1 a2 B1 b1 Σ1 σ1"));
+        assert_eq!(total, 7);
+        let (result, total) = character_frequencies_with_total("AaaBbΣσ", CaseSense::Insensitive);
+        assert_eq!(result, expected_freq("a3 b2 σ2"));
+        assert_eq!(total, 7);
+        assert_eq!(total, result.values().sum::<usize>());
+    }
+
+    #[test]
+    fn test_character_frequencies_skipping() {
+        let text = "a b\tc,\u{7}D!\n.";
+        let case = CaseSense::InsensitiveASCIIOnly;
+        let result = character_frequencies_skipping(text, case, SkipSet::NONE);
+        assert_eq!(result, character_frequencies_w_case(text, case));
+        let result = character_frequencies_skipping(text, case, SkipSet::SKIP_WHITESPACE);
+        assert_eq!(result, expected_freq("a1 b1 c1 ,1 \u{7}1 d1 !1 .1"));
+        let result = character_frequencies_skipping(text, case, SkipSet::SKIP_CONTROL);
+        let mut expected = expected_freq("a1 b1 c1 ,1 d1 !1 .1");
+        expected.insert(' ', 1);
+        assert_eq!(result, expected);
+        let result = character_frequencies_skipping(text, case, SkipSet::SKIP_PUNCTUATION);
+        let mut expected = expected_freq("a1 b1 c1 \u{7}1 d1");
+        expected.insert(' ', 1);
+        expected.insert('\t', 1);
+        expected.insert('\n', 1);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_character_frequencies_skipping_combined() {
+        let text = "a b\tc,\u{7}D!\n.";
+        let case = CaseSense::Sensitive;
+        let skip = SkipSet::SKIP_WHITESPACE | SkipSet::SKIP_CONTROL;
+        let result = character_frequencies_skipping(text, case, skip);
+        assert_eq!(result, expected_freq("a1 b1 c1 ,1 D1 !1 .1"));
+        let mut skip = skip;
+        skip |= SkipSet::SKIP_PUNCTUATION;
+        let result = character_frequencies_skipping(text, case, skip);
+        assert_eq!(result, expected_freq("a1 b1 c1 D1"));
+        let result = character_frequencies_skipping(" \t\n", case, skip);
+        assert_eq!(result, HashMap::new());
+    }
+
+    #[test]
+    fn test_weighted_character_frequencies() {
+        let vowels_double = |ch| if "aeiou".contains(ch) { 2 } else { 1 };
+        let result = weighted_character_frequencies(
+            "bAnana",
+            CaseSense::InsensitiveASCIIOnly,
+            vowels_double,
+        );
+        assert_eq!(result, expected_freq("b1 a6 n2"));
+        let result = weighted_character_frequencies("bAnana", CaseSense::Sensitive, vowels_double);
+        assert_eq!(result, expected_freq("b1 A1 a4 n2"));
+    }
+
+    #[test]
+    fn test_weighted_character_frequencies_zero_weight() {
+        let only_vowels = |ch| if "aeiou".contains(ch) { 1 } else { 0 };
+        let result = weighted_character_frequencies("banana", CaseSense::Sensitive, only_vowels);
+        assert_eq!(result, expected_freq("a3"));
+        let result = weighted_character_frequencies("banana", CaseSense::Sensitive, |_| 0);
+        assert_eq!(result, HashMap::new());
+    }
+
+    #[test]
+    fn test_weighted_character_frequencies_expanding() {
+        let result = weighted_character_frequencies("İi", CaseSense::Insensitive, |_| 2);
+        assert_eq!(result, expected_freq("i4 \u{307}2"));
+        let result = weighted_character_frequencies("ßa", CaseSense::InsensitiveUppercase, |_| 1);
+        assert_eq!(result, expected_freq("S2 A1"));
+        #[cfg(feature = "caseless")]
+        {
+            let weight = |ch| if ch == 's' { 3 } else { 1 };
+            let result = weighted_character_frequencies("Straße", CaseSense::CaseFold, weight);
+            assert_eq!(result, expected_freq("s9 t1 r1 a1 e1"));
+        }
+    }
+
+    #[test]
+    fn test_character_frequencies_with_progress() {
+        let text = "ab".repeat(PROGRESS_INTERVAL * 2);
+        let cancel = AtomicBool::new(false);
+        let mut reported = Vec::new();
+        let result = character_frequencies_with_progress(
+            &text,
+            CaseSense::Sensitive,
+            |processed| reported.push(processed),
+            &cancel,
+        );
+        assert_eq!(
+            result,
+            character_frequencies_w_case(&text, CaseSense::Sensitive)
+        );
+        let interval = PROGRESS_INTERVAL;
+        assert_eq!(
+            reported,
+            [
+                interval,
+                2 * interval,
+                3 * interval,
+                4 * interval,
+                4 * interval
+            ]
+        );
+    }
+
+    #[test]
+    fn test_character_frequencies_with_progress_cancelled() {
+        let text = "abc".repeat(100_000);
+        let cancel_after = 10_000;
+        let cancel = AtomicBool::new(false);
+        let result = character_frequencies_with_progress(
+            &text,
+            CaseSense::Sensitive,
+            |processed| {
+                if processed >= cancel_after {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            },
+            &cancel,
+        );
+        let total: usize = result.values().sum();
+        assert!(total >= cancel_after);
+        assert!(total < cancel_after + PROGRESS_INTERVAL);
+        assert!(result.values().all(|&frequency| frequency * 3 >= total - 2));
+    }
+
+    #[test]
+    fn test_character_frequencies_sorted() {
+        let result = character_frequencies_sorted("dbca|AσΣAb", CaseSense::Sensitive);
+        let expected = [
+            ('A', 2),
+            ('a', 1),
+            ('b', 2),
+            ('c', 1),
+            ('d', 1),
+            ('|', 1),
+            ('Σ', 1),
+            ('σ', 1),
+        ];
+        assert_eq!(result.into_iter().collect::<Vec<_>>(), expected);
+        let result = character_frequencies_sorted("dbca|AσΣAb", CaseSense::Insensitive);
+        let keys: Vec<char> = result.keys().copied().collect();
+        assert_eq!(keys, ['a', 'b', 'c', 'd', '|', 'σ']);
+    }
+
+    #[test]
+    fn test_count_char_ascii() {
+        let text = "AaaBbΣ|a";
+        assert_eq!(count_char(text, 'a', CaseSense::Sensitive), 3);
+        assert_eq!(count_char(text, 'A', CaseSense::Sensitive), 1);
+        assert_eq!(count_char(text, 'A', CaseSense::InsensitiveASCIIOnly), 4);
+        assert_eq!(count_char(text, 'a', CaseSense::Insensitive), 4);
+        assert_eq!(count_char(text, 'a', CaseSense::InsensitiveUppercase), 4);
+        assert_eq!(count_char(text, 'z', CaseSense::Insensitive), 0);
+        assert_eq!(count_char("", 'a', CaseSense::Insensitive), 0);
+    }
+
+    #[test]
+    fn test_count_char_greek() {
+        let text = "ὀδυσσεύςὈΔΥΣΣΕΎΣ";
+        assert_eq!(count_char(text, 'σ', CaseSense::Sensitive), 2);
+        assert_eq!(count_char(text, 'Σ', CaseSense::Sensitive), 3);
+        assert_eq!(count_char(text, 'Σ', CaseSense::InsensitiveASCIIOnly), 3);
+        assert_eq!(count_char(text, 'Σ', CaseSense::Insensitive), 5);
+        assert_eq!(count_char(text, 'σ', CaseSense::Insensitive), 5);
+        assert_eq!(count_char(text, 'ς', CaseSense::Insensitive), 1);
+        assert_eq!(count_char(text, 'σ', CaseSense::InsensitiveUppercase), 6);
+    }
+
+    #[test]
+    fn test_char_boundary_ranges() {
+        let text = "ὀδυσσεύςὈΔΥΣΣΕΎΣ";
+        for parts in [1, 2, 3, 7, 16, 100] {
+            let ranges = char_boundary_ranges(text, parts);
+            assert!(ranges.len() <= parts);
+            assert_eq!(ranges.first().unwrap().start, 0);
+            assert_eq!(ranges.last().unwrap().end, text.len());
+            assert!(ranges.windows(2).all(|pair| pair[0].end == pair[1].start));
+            assert_eq!(
+                ranges
+                    .iter()
+                    .map(|range| &text[range.clone()])
+                    .collect::<String>(),
+                text
+            );
+        }
+        assert!(char_boundary_ranges("", 4).is_empty());
+    }
+
+    #[test]
+    fn test_char_boundary_ranges_lines() {
+        let text = "first line\nsecond líne\nthird\n\nfifth ὀδυσσεύς\n".repeat(20);
+        let expected = sequential_character_frequencies(&text);
+        for parts in [2, 3, 8, 50] {
+            let ranges = char_boundary_ranges(&text, parts);
+            assert_eq!(ranges.last().unwrap().end, text.len());
+            assert!(ranges
+                .iter()
+                .all(|range| text[range.clone()].ends_with('\n')));
+            assert_eq!(character_frequencies_with_n_threads(&text, parts), expected);
+        }
+
+        let text = "ὀδυσσεύς".repeat(100);
+        let expected = sequential_character_frequencies(&text);
+        for parts in [2, 3, 8, 50] {
+            let ranges = char_boundary_ranges(&text, parts);
+            assert!(ranges.windows(2).all(|pair| pair[0].end == pair[1].start));
+            assert!(ranges
+                .iter()
+                .all(|range| text.is_char_boundary(range.start)));
+            assert_eq!(character_frequencies_with_n_threads(&text, parts), expected);
+        }
+    }
+
+    #[test]
+    fn test_frequency_map_display() {
+        let frequency_map = FrequencyMap::from(character_frequencies_w_case(
+            "aaaaaaaaaabb\tc\u{7}σ'",
+            CaseSense::Sensitive,
+        ));
+        let expected = concat!(
+            "'a'      10\n",
+            "'b'       2\n",
+            "'\\u{7}'   1\n",
+            "'\\t'      1\n",
+            "'\\''      1\n",
+            "'c'       1\n",
+            "'σ'       1\n",
+            "total    17\n",
+        );
+        assert_eq!(frequency_map.to_string(), expected);
+    }
+
+    #[test]
+    fn test_frequency_map_display_empty() {
+        assert_eq!(FrequencyMap::default().to_string(), "total  0\n");
+    }
+
+    #[test]
+    fn test_frequency_map_conversions() {
+        let frequency_map = FrequencyMap::from(expected_freq("a2 b1"));
+        assert_eq!(frequency_map[&'a'], 2);
+        assert_eq!(frequency_map.len(), 2);
+        assert_eq!(HashMap::from(frequency_map.clone()), expected_freq("a2 b1"));
+        assert_eq!(frequency_map.into_inner(), expected_freq("a2 b1"));
+    }
+
+    #[test]
+    fn test_frequency_map_get_insensitive() {
+        let sensitive = character_frequencies_w_case("aAbB", CaseSense::Sensitive);
+        let frequency_map = FrequencyMap::from(sensitive);
+        assert_eq!(frequency_map.get_insensitive('a'), 2);
+        assert_eq!(frequency_map.get_insensitive('A'), 2);
+        assert_eq!(frequency_map.get_insensitive('b'), 2);
+        assert_eq!(frequency_map.get_insensitive('c'), 0);
+        assert_eq!(frequency_map[&'a'], 1);
+
+        let frequency_map = FrequencyMap::from(expected_freq("Σ1 σ2 ς3 ß1 İ1 i2 I4 11"));
+        assert_eq!(frequency_map.get_insensitive('σ'), 6);
+        assert_eq!(frequency_map.get_insensitive('ς'), 6);
+        assert_eq!(frequency_map.get_insensitive('ß'), 1);
+        assert_eq!(frequency_map.get_insensitive('i'), 6);
+        assert_eq!(frequency_map.get_insensitive('İ'), 1);
+        assert_eq!(frequency_map.get_insensitive('1'), 1);
+    }
+
+    #[test]
+    fn test_character_frequencies_normalized_newlines() {
+        let unix = "first line\nsecond\n\nlast";
+        let windows = "first line\r\nsecond\r\n\r\nlast";
+        let mac = "first line\rsecond\r\rlast";
+        let case = CaseSense::Sensitive;
+        let expected = character_frequencies_w_case(unix, case);
+        assert_eq!(
+            character_frequencies_normalized_newlines(unix, case),
+            expected
+        );
+        assert_eq!(
+            character_frequencies_normalized_newlines(windows, case),
+            expected
+        );
+        assert_eq!(
+            character_frequencies_normalized_newlines(mac, case),
+            expected
+        );
+        assert_ne!(character_frequencies_w_case(windows, case), expected);
+    }
+
+    #[test]
+    fn test_normalize_newlines_split_between_threads() {
+        let windows = "a\r\n".repeat(7);
+        for threads in 1..=21 {
+            let result = character_frequencies_with_n_threads_w_case(
+                normalize_newlines(&windows),
+                threads,
+                CaseSense::Sensitive,
+            );
+            let mut expected = expected_freq("a7");
+            expected.insert('\n', 7);
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "memmap2")]
+    fn test_character_frequencies_mmap() {
+        let text = "ὀδυσσεύς Hello, World!\n".repeat(1000);
+        let paths = temp_files("mmap", &[&text, ""]);
+        let result = character_frequencies_mmap(&paths[0], CaseSense::Sensitive);
+        let result_empty = character_frequencies_mmap(&paths[1], CaseSense::Sensitive);
+        paths.iter().for_each(|path| fs::remove_file(path).unwrap());
+        assert!(text.len() > 4096);
+        assert_eq!(
+            result.unwrap(),
+            character_frequencies_w_case(&text, CaseSense::Sensitive)
+        );
+        assert_eq!(result_empty.unwrap(), HashMap::new());
+    }
+
+    #[test]
+    #[cfg(feature = "memmap2")]
+    fn test_character_frequencies_mmap_invalid_utf8() {
+        let path = std::env::temp_dir().join(format!(
+            "character_frequency_mmap_invalid_{}.txt",
+            std::process::id()
+        ));
+        let mut bytes = "σ".repeat(5000).into_bytes();
+        bytes[7001] = 0xFF;
+        fs::write(&path, bytes).unwrap();
+        let result = character_frequencies_mmap(&path, CaseSense::Sensitive);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_utf8_boundary_ranges_invalid() {
+        let bytes = [b'a', 0xCF, 0x83, 0xFF, 0x83, b'b'];
+        for parts in 1..8 {
+            let ranges = utf8_boundary_ranges(&bytes, parts);
+            assert_eq!(ranges.first().unwrap().start, 0);
+            assert_eq!(ranges.last().unwrap().end, bytes.len());
+            assert!(ranges.iter().all(|range| bytes[range.start] & 0xC0 != 0x80));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-properties")]
+    fn test_category_frequencies() {
+        let result = category_frequencies("Hello, 42 $+ Ωμέγα!");
+        let expected = HashMap::from([
+            (GeneralCategory::UppercaseLetter, 2),
+            (GeneralCategory::LowercaseLetter, 8),
+            (GeneralCategory::DecimalNumber, 2),
+            (GeneralCategory::OtherPunctuation, 2),
+            (GeneralCategory::CurrencySymbol, 1),
+            (GeneralCategory::MathSymbol, 1),
+            (GeneralCategory::SpaceSeparator, 3),
+        ]);
+        assert_eq!(result, expected);
+        assert_eq!(category_frequencies(""), HashMap::new());
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-script")]
+    fn test_script_frequencies() {
+        let frequency_map = script_frequencies("abc αβ 日本語e\u{301}");
+        let expected: HashMap<Script, usize> = [
+            (Script::Latin, 4),
+            (Script::Greek, 2),
+            (Script::Han, 3),
+            (Script::Common, 2),
+            (Script::Inherited, 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(frequency_map, expected);
+        assert_eq!(script_frequencies(""), HashMap::new());
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-blocks")]
+    fn test_block_frequencies() {
+        let frequency_map = block_frequencies("abcαβγδ日本");
+        let expected = HashMap::from([
+            ("Basic Latin", 3),
+            ("Greek and Coptic", 4),
+            ("CJK Unified Ideographs", 2),
+        ]);
+        assert_eq!(frequency_map, expected);
+        assert_eq!(block_frequencies("ἀ\u{e0080}")["No_Block"], 1);
+        assert_eq!(block_frequencies(""), HashMap::new());
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-script")]
+    fn test_character_frequencies_with_script_check() {
+        let (frequency_map, mixed) = character_frequencies_with_script_check("paypal.com 2024!");
+        assert_eq!(
+            frequency_map,
+            character_frequencies_w_case("paypal.com 2024!", CaseSense::Sensitive)
+        );
+        assert!(!mixed);
+
+        // Cyrillic 'а' and 'о' looking like the Latin ones
+        let (frequency_map, mixed) =
+            character_frequencies_with_script_check("p\u{430}ypal.c\u{43e}m");
+        assert_eq!(frequency_map[&'\u{430}'], 1);
+        assert_eq!(frequency_map[&'a'], 1);
+        assert!(mixed);
+
+        assert!(character_frequencies_with_script_check("e\u{301} 日本").1);
+        assert!(!character_frequencies_with_script_check("").1);
+        assert!(!character_frequencies_with_script_check("κόσμε, 123").1);
+    }
+
+    #[test]
+    fn test_grouped_frequencies() {
+        let classify = |ch: char| match ch {
+            'a' | 'e' | 'i' | 'o' | 'u' => "vowel",
+            'a'..='z' => "consonant",
+            '0'..='9' => "digit",
+            _ => "symbol",
+        };
+        let groups = grouped_frequencies("hello123", classify);
+        let expected = HashMap::from([("vowel", 2), ("consonant", 3), ("digit", 3)]);
+        assert_eq!(groups, expected);
+        let groups =
+            grouped_frequencies("hello123!?".repeat(PARALLEL_THRESHOLD).as_str(), classify);
+        assert_eq!(groups["symbol"], 2 * PARALLEL_THRESHOLD);
+        assert_eq!(groups["digit"], 3 * PARALLEL_THRESHOLD);
+        assert_eq!(grouped_frequencies("", classify), HashMap::new());
+    }
+
+    #[test]
+    fn test_frequencies_by_key_with_n_threads() {
+        let text = "ὀδυσσεύς 123";
+        for threads in [1, 2, 5, 50] {
+            let result = frequencies_by_key_with_n_threads(text, threads, |ch| ch.is_alphabetic());
+            assert_eq!(result, HashMap::from([(true, 8), (false, 4)]));
+        }
+    }
+
+    #[test]
+    fn test_worker_panic_is_resumed() {
+        let text = "abc\ndef\nxyz\n".repeat(100);
+        let panic = std::panic::catch_unwind(|| {
+            frequencies_by_key_with_n_threads(&text, 4, |ch| match ch {
+                'x' => panic!("bad char x"),
+                _ => ch,
+            })
+        })
+        .unwrap_err();
+        assert_eq!(panic_message(panic), "bad char x");
+
+        let text = "aİ".repeat(100);
+        let panic = std::panic::catch_unwind(|| {
+            character_frequencies_with_n_threads_w_case(&text, 4, CaseSense::Insensitive)
+        })
+        .unwrap_err();
+        assert!(panic_message(panic).contains("MultiCharacterLowercase('İ')"));
+        let result =
+            try_character_frequencies_with_n_threads_w_case(&text, 4, CaseSense::Insensitive);
+        assert_eq!(result, Err(FreqError::MultiCharacterLowercase('İ')));
+    }
+
+    #[test]
+    #[cfg(not(feature = "single-thread"))]
+    fn test_for_each_completed_order() {
+        // the first item waits until the result of the second one has been received
+        let received = AtomicBool::new(false);
+        let mut order = Vec::new();
+        for_each_completed(
+            vec![0, 1],
+            |item| {
+                while item == 0 && !received.load(Ordering::Acquire) {
+                    thread::yield_now();
+                }
+                item
+            },
+            |result| {
+                order.push(result.unwrap());
+                received.store(true, Ordering::Release);
+            },
+        );
+        assert_eq!(order, vec![1, 0]);
+    }
+
+    #[test]
+    fn test_unicode_case_insensitive_turkish() {
+        let turkish = "IıİiIĞğ";
+        let turkish_case = CaseSense::InsensitiveLocale(Locale::Turkish);
+        let result = character_frequencies_w_case(turkish, turkish_case);
+        assert_eq!(result, expected_freq("ı3 i2 ğ2"));
+        let result = character_frequencies_w_case("IıiIĞğ", CaseSense::Insensitive);
+        assert_eq!(result, expected_freq("i3 ı1 ğ2"));
+    }
+
+    #[test]
+    fn test_unicode_case_insensitive_azerbaijani() {
+        let azerbaijani_case = CaseSense::InsensitiveLocale(Locale::Azerbaijani);
+        let result = character_frequencies_w_case("IıİiƏə", azerbaijani_case);
+        assert_eq!(result, expected_freq("ı2 i2 ə2"));
+    }
+
+    #[test]
+    #[cfg(feature = "caseless")]
+    fn test_case_fold() {
+        let result = character_frequencies_w_case("σςΣ", CaseSense::CaseFold);
+        assert_eq!(result, expected_freq("σ3"));
+        let result = character_frequencies_w_case("σς", CaseSense::Insensitive);
+        assert_eq!(result, expected_freq("σ1 ς1"));
+        let result = character_frequencies_with_n_threads_w_case("Straße", 3, CaseSense::CaseFold);
+        assert_eq!(result, expected_freq("s3 t1 r1 a1 e1"));
+        let result = sequential_character_frequencies_w_case("ẞİ", CaseSense::CaseFold);
+        assert_eq!(result, expected_freq("s2 i1 \u{307}1"));
+        let result = try_fold_case('ß', CaseSense::CaseFold);
+        assert_eq!(result, Err(FreqError::MultiCharacterCaseFold('ß')));
+    }
+
+    #[test]
+    fn test_case_insensitive_uppercase() {
+        let result = character_frequencies_w_case("aAbB", CaseSense::InsensitiveUppercase);
+        assert_eq!(result, expected_freq("A2 B2"));
+        let result = character_frequencies_w_case("aAbB", CaseSense::InsensitiveASCIIOnlyUppercase);
+        assert_eq!(result, expected_freq("A2 B2"));
+    }
+
+    #[test]
+    fn test_unicode_case_insensitive_uppercase() {
+        let greek_mix = "ὀδυσσεύςὈΔΥΣΣΕΎΣ";
+        let result = character_frequencies_w_case(greek_mix, CaseSense::InsensitiveUppercase);
+        assert_eq!(result, expected_freq("Ὀ2 Δ2 Υ2 Σ6 Ε2 Ύ2"));
+        let case = CaseSense::InsensitiveASCIIOnlyUppercase;
+        let result = character_frequencies_w_case(greek_mix, case);
+        assert_eq!(
+            result,
+            expected_freq("Ὀ1 Δ1 Υ1 Σ3 Ε1 Ύ1 ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1")
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unicode_case_insensitive_uppercase_multicharacter() {
+        sequential_character_frequencies_w_case("ß", CaseSense::InsensitiveUppercase);
+    }
+
+    #[test]
+    fn test_merge_into_largest() {
+        let maps: Vec<CharMap> = ["ab", "abcde", "", "ea"]
+            .iter()
+            .map(|text| count_into_new(text))
+            .collect();
+        let merged = into_std_map(merge_into_largest(maps));
+        assert_eq!(merged, expected_freq("a3 b2 c1 d1 e2"));
+        assert_eq!(merge_into_largest(Vec::new()), CharMap::default());
+    }
+
+    #[test]
+    fn test_many_threads_same_as_sequential() {
+        let text = "The quick brown fox jumps over the lazy dog. ¿Qué tal? 日本語";
+        let expected = sequential_character_frequencies(text);
+        for threads in [2, 3, 7, 16, 32] {
+            assert_eq!(
+                character_frequencies_with_n_threads(text, threads),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_character_frequencies_sampled() {
+        let text = "ὈΔΥΣΣΕΎΣ and Hello, World! ".repeat(500);
+        for case in [CaseSense::Sensitive, CaseSense::Insensitive] {
+            let exact = character_frequencies_w_case(&text, case);
+            assert_eq!(character_frequencies_sampled(&text, case, 1.0, 7), exact);
+            assert_eq!(
+                character_frequencies_sampled(&text, case, 0.0, 7),
+                HashMap::new()
+            );
+        }
+
+        let case = CaseSense::Sensitive;
+        let sample = character_frequencies_sampled(&text, case, 0.25, 7);
+        assert_eq!(sample, character_frequencies_sampled(&text, case, 0.25, 7));
+        assert_ne!(sample, character_frequencies_sampled(&text, case, 0.25, 8));
+        let sampled: usize = sample.values().sum();
+        let total = text.chars().count();
+        assert!(sampled > total / 5 && sampled < total * 3 / 10);
+        assert_eq!(
+            character_frequencies_sampled("", case, 0.5, 7),
+            HashMap::new()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "testutil")]
+    fn test_parallel_matches_sequential_random() {
+        let alphabet = [
+            'a', 'B', '\n', ' ', 'é', 'Σ', 'ς', '€', '日', '𝄞', '\u{301}',
+        ];
+        let mut state = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..200 {
+            let len = next_random(&mut state) % 300;
+            let text: String = (0..len)
+                .map(|_| match next_random(&mut state) % 4 {
+                    0 => char::from_u32((next_random(&mut state) % 0x11_0000) as u32)
+                        .filter(|ch| ch.to_lowercase().len() == 1)
+                        .unwrap_or('?'),
+                    _ => alphabet[(next_random(&mut state) % alphabet.len() as u64) as usize],
+                })
+                .collect();
+            let threads = (next_random(&mut state) % 17) as usize;
+            for case in [CaseSense::Sensitive, CaseSense::Insensitive] {
+                assert_parallel_matches_sequential(&text, threads, case);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dominant_case() {
+        let frequency_map = character_frequencies_w_dominant_case("AAaBbb", CaseSense::Insensitive);
+        let expected: HashMap<char, (usize, char)> =
+            [('a', (3, 'A')), ('b', (3, 'b'))].into_iter().collect();
+        assert_eq!(frequency_map, expected);
+
+        let frequency_map = character_frequencies_w_dominant_case("AaBb", CaseSense::Insensitive);
+        assert_eq!(frequency_map[&'a'], (2, 'a'));
+        assert_eq!(frequency_map[&'b'], (2, 'b'));
+
+        let frequency_map = character_frequencies_w_dominant_case("Aab", CaseSense::Sensitive);
+        assert_eq!(frequency_map[&'A'], (1, 'A'));
+        assert_eq!(frequency_map[&'a'], (1, 'a'));
+    }
+
+    #[test]
+    fn test_streaming_counter() {
+        let mut counter = StreamingCounter::new(CaseSense::InsensitiveASCIIOnly);
+        counter.push("Ab");
+        counter.push("");
+        counter.push("a");
+        assert_eq!(counter.frequencies(), expected_freq("a2 b1"));
+        assert_eq!(counter.finish(), expected_freq("a2 b1"));
+    }
+
+    #[test]
+    fn test_streaming_counter_remove() {
+        let mut counter = StreamingCounter::new(CaseSense::Insensitive);
+        counter.push("Hello, ΣΣ");
+        counter.remove("Hello, ΣΣ");
+        assert_eq!(counter.frequencies(), HashMap::new());
+
+        counter.push("first line\n");
+        counter.push("SECOND line\n");
+        counter.remove("FIRST LINE\n");
+        let expected = character_frequencies_w_case("second line\n", CaseSense::Insensitive);
+        assert_eq!(counter.frequencies(), expected);
+        counter.remove("zzz second second");
+        assert_eq!(counter.clone().finish(), expected_freq("l1 i1 \n1"));
+
+        counter.push(&"€".as_bytes()[..1]);
+        counter.remove("line\n");
+        counter.push(&"€".as_bytes()[1..]);
+        assert_eq!(counter.finish(), expected_freq("€1"));
+    }
+
+    #[test]
+    fn test_streaming_counter_split_multibyte() {
+        let text = "a€b日";
+        let mut counter = StreamingCounter::new(CaseSense::Sensitive);
+        for byte in text.as_bytes() {
+            counter.push(std::slice::from_ref(byte));
+        }
+        assert_eq!(counter.finish(), expected_freq("a1 €1 b1 日1"));
+
+        let mut counter = StreamingCounter::new(CaseSense::Sensitive);
+        counter.push(&"€".as_bytes()[..1]);
+        assert_eq!(counter.frequencies(), HashMap::new());
+        counter.push(&"€".as_bytes()[1..]);
+        assert_eq!(counter.frequencies(), expected_freq("€1"));
+    }
+
+    #[test]
+    fn test_streaming_counter_invalid_utf8() {
+        let mut counter = StreamingCounter::new(CaseSense::Sensitive);
+        counter.push(&[b'a', 0xff, b'b']);
+        counter.push(&"€".as_bytes()[..2]);
+        assert_eq!(counter.finish(), expected_freq("a1 b1 \u{fffd}2"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn test_character_frequencies_stream() {
+        let bytes = "Wörld日本".as_bytes();
+        // 'ö' and '日' are split between chunks
+        let chunks = vec![
+            Ok(Bytes::from_static(&bytes[..2])),
+            Ok(Bytes::from_static(&bytes[2..7])),
+            Ok(Bytes::from_static(&bytes[7..])),
+        ];
+        let stream = futures::stream::iter(chunks);
+        let frequency_map = character_frequencies_stream(stream, CaseSense::Sensitive).await;
+        assert_eq!(
+            frequency_map.unwrap(),
+            expected_freq("W1 ö1 r1 l1 d1 日1 本1")
+        );
+
+        let large = "Σσ".repeat(PARALLEL_THRESHOLD);
+        let chunks = vec![Ok(Bytes::from("Σ")), Ok(Bytes::from(large.clone()))];
+        let stream = futures::stream::iter(chunks);
+        let frequency_map = character_frequencies_stream(stream, CaseSense::Insensitive).await;
+        let expected = character_frequencies_w_case(format!("Σ{}", large), CaseSense::Insensitive);
+        assert_eq!(frequency_map.unwrap(), expected);
+
+        let chunks = vec![
+            Ok(Bytes::from("ab")),
+            Err(io::Error::new(io::ErrorKind::UnexpectedEof, "closed")),
+        ];
+        let stream = futures::stream::iter(chunks);
+        let result = character_frequencies_stream(stream, CaseSense::Sensitive).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        let stream = futures::stream::iter(Vec::<io::Result<Bytes>>::new());
+        let frequency_map = character_frequencies_stream(stream, CaseSense::Sensitive).await;
+        assert_eq!(frequency_map.unwrap(), HashMap::new());
+    }
+
+    #[test]
+    fn test_bottom_n() {
+        let frequency_map = expected_freq("a4 b1 c3 d2");
+        assert_eq!(bottom_n(&frequency_map, 2), vec![('b', 1), ('d', 2)]);
+        assert_eq!(
+            bottom_n(&frequency_map, 10),
+            vec![('b', 1), ('d', 2), ('c', 3), ('a', 4)]
+        );
+        assert_eq!(bottom_n(&frequency_map, 0), vec![]);
+        assert_eq!(bottom_n(&HashMap::new(), 3), vec![]);
+    }
+
+    #[test]
+    fn test_bottom_n_ties() {
+        let frequency_map = expected_freq("z1 a1 m1 b2 c2");
+        assert_eq!(
+            bottom_n(&frequency_map, 3),
+            vec![('a', 1), ('m', 1), ('z', 1)]
+        );
+        assert_eq!(bottom_n(&frequency_map, 4)[3], ('b', 2));
+    }
+
+    #[test]
+    fn test_counter_chunk_chars() {
+        let text = "Hello, Wörld! ¿Qué tal? 日本語のテキスト 𝄞𝄞";
+        let expected = sequential_character_frequencies(text);
+        for chunk_chars in [0, 1, 2, 3, 7, 100, 1000] {
+            for threads in [1, 2, 5] {
+                let counter = Counter::new().threads(threads).chunk_chars(chunk_chars);
+                assert_eq!(counter.count(text), expected);
+            }
+        }
+        assert_eq!(Counter::new().chunk_chars(4).count(""), HashMap::new());
+    }
+
+    #[test]
+    fn test_counter_tasks() {
+        let text = "Hello, Wörld!\n¿Qué tal?\n日本語のテキスト 𝄞𝄞".repeat(50);
+        let expected = sequential_character_frequencies(&text);
+        for tasks in [0, 1, 2, 7, 500, 10_000] {
+            for threads in [1, 3] {
+                let counter = Counter::new().threads(threads).tasks(tasks);
+                let (frequency_map, stats) = counter.count_with_stats(&text);
+                assert_eq!(frequency_map, expected);
+                assert!(stats.chunks <= max(1, tasks));
+                assert!(stats.threads <= threads);
+            }
+        }
+        let (_, stats) = Counter::new().threads(2).tasks(100).count_with_stats(&text);
+        assert_eq!((stats.threads, stats.chunks), (2, 100));
+        let counter = Counter::new().chunk_chars(1).tasks(3);
+        assert_eq!(counter.count_with_stats(&text).1.chunks, 3);
+        assert_eq!(Counter::new().tasks(4).count(""), HashMap::new());
+    }
+
+    #[test]
+    fn test_counter_deterministic_order() {
+        let text = "Hello,  World!\n\t ¿Qué tal? 日本語 ὈΔΥΣΣΕΎΣ\n".repeat(200);
+        let expected = ranked_frequencies(&text, CaseSense::Sensitive, Tiebreak::ByFirstOccurrence);
+        for threads in [1, 2, 3, 8] {
+            for tasks in [1, 7, 100] {
+                let counter = Counter::new()
+                    .threads(threads)
+                    .case(CaseSense::Sensitive)
+                    .tasks(tasks);
+                let ordered = counter.clone().deterministic_order(true);
+                assert_eq!(ordered.count(&text), counter.count(&text));
+                let ranked = ordered.count_ranked(&text, Tiebreak::ByFirstOccurrence);
+                assert_eq!(ranked, expected);
+                let ranked = counter.count_ranked(&text, Tiebreak::ByFirstOccurrence);
+                assert_eq!(ranked, expected);
+
+                let collapsed = counter.clone().collapse_whitespace(true);
+                let ordered = collapsed.clone().deterministic_order(true);
+                assert_eq!(ordered.count(&text), collapsed.count(&text));
+            }
+        }
+        let ranked = Counter::new().count_ranked("zzaa b", Tiebreak::ByChar);
+        assert_eq!(ranked, vec![('a', 2), ('z', 2), (' ', 1), ('b', 1)]);
+        let counter = Counter::new().deterministic_order(true);
+        assert_eq!(counter.count(""), HashMap::new());
+        assert_eq!(
+            counter.count_ranked("", Tiebreak::ByFirstOccurrence),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_counter() {
+        let text = "AaBbİ";
+        let counter = Counter::new().threads(3).case(CaseSense::Sensitive);
+        assert_eq!(counter.count(text), expected_freq("A1 a1 B1 b1 İ1"));
+        let counter = counter.case(CaseSense::Insensitive);
+        let expected = Err(FreqError::MultiCharacterLowercase('İ'));
+        assert_eq!(counter.try_count(text), expected);
+        assert_eq!(counter.chunk_chars(2).try_count(text), expected);
+    }
+
+    #[test]
+    fn test_utf16_unit_frequencies() {
+        let frequency_map = utf16_unit_frequencies("a𝄞é𝄞");
+        let expected: HashMap<u16, usize> = [(0x0061, 1), (0x00E9, 1), (0xD834, 2), (0xDD1E, 2)]
+            .into_iter()
+            .collect();
+        assert_eq!(frequency_map, expected);
+        assert_eq!(
+            frequency_map.values().sum::<usize>(),
+            "a𝄞é𝄞".encode_utf16().count()
+        );
+        assert_eq!(utf16_unit_frequencies(""), HashMap::new());
+    }
+
+    #[test]
+    fn test_default_threads() {
+        assert!(default_threads() >= 1);
+        let text = "Hello, World! ¿Qué tal? 日本語";
+        let expected = sequential_character_frequencies_w_case(text, CaseSense::Sensitive);
+        assert_eq!(
+            character_frequencies_w_case(text, CaseSense::Sensitive),
+            expected
+        );
+        let expected = sequential_character_frequencies(text);
+        assert_eq!(character_frequencies(text), expected);
+    }
+
+    #[test]
+    fn test_frequencies_by_key() {
+        let frequency_map = frequencies_by_key("a1b22c333", |ch| ch.is_ascii_digit());
+        let expected: HashMap<bool, usize> = [(true, 6), (false, 3)].into_iter().collect();
+        assert_eq!(frequency_map, expected);
+        assert_eq!(frequencies_by_key("", |ch| ch), HashMap::new());
+
+        let text = "Hello, WORLD! ¿Qué TAL?";
+        let frequency_map = frequencies_by_key(text, |ch| ch.to_ascii_lowercase());
+        assert_eq!(frequency_map, sequential_character_frequencies(text));
     }
 
-    let mut from = 0;
-    for _ in 0..threads_with_less_data {
-        generate_counting_thread(from, chunk_size, &tx, &shared, case);
-        from += chunk_size;
-    }
-    for _ in 0..threads_with_more_data {
-        generate_counting_thread(from, chunk_size + 1, &tx, &shared, case);
-        from += chunk_size + 1;
-    }
-
-    fn generate_adding_thread(
-        a: HashMap<char, usize>,
-        b: HashMap<char, usize>,
-        tx: &Sender<HashMap<char, usize>>,
-    ) {
-        let tx = tx.clone();
-        thread::spawn(move || {
-            let sum = add_frequencies(a, b);
-            tx.send(sum).unwrap();
-        });
+    #[test]
+    fn test_character_frequencies_within() {
+        let alphabet: HashSet<char> = ['a', 'b', 'c'].into_iter().collect();
+        let frequency_map = character_frequencies_within("aabxyz", &alphabet, CaseSense::Sensitive);
+        assert_eq!(frequency_map, expected_freq("a2 b1 c0"));
+        let frequency_map = character_frequencies_within("AaB", &alphabet, CaseSense::Sensitive);
+        assert_eq!(frequency_map, expected_freq("a1 b0 c0"));
+        let frequency_map = character_frequencies_within("", &HashSet::new(), CaseSense::Sensitive);
+        assert_eq!(frequency_map, HashMap::new());
     }
 
-    let mut waiting_num: usize = threads;
-    let mut received = Vec::with_capacity(2);
-    while waiting_num > 0 {
-        received.push(rx.recv().unwrap());
-        waiting_num -= 1;
+    #[test]
+    fn test_character_frequencies_in_range() {
+        let result = character_frequencies_in_range("abcdef", 'a'..='c', CaseSense::Sensitive);
+        assert_eq!(result, expected_freq("a1 b1 c1"));
+        let result = character_frequencies_in_range("ABCdef", 'a'..='c', CaseSense::Sensitive);
+        assert_eq!(result, HashMap::new());
+        let case = CaseSense::InsensitiveASCIIOnly;
+        let result = character_frequencies_in_range("ABCabf", 'a'..='c', case);
+        assert_eq!(result, expected_freq("a2 b2 c1"));
+    }
 
-        if received.len() >= 2 {
-            generate_adding_thread(
-                received.pop().unwrap(),
-                received.pop().unwrap(),
-                &tx.clone(),
-            );
-            waiting_num += 1;
+    #[test]
+    fn test_count_options() {
+        let text = "Hello, WORLD! ὈΔΥΣΣΕΎΣ\n".repeat(100);
+        assert_eq!(
+            count(&text, &CountOptions::default()),
+            character_frequencies(&text)
+        );
+        let options = CountOptions::default()
+            .threads(3)
+            .case(CaseSense::Insensitive);
+        assert_eq!(
+            count(&text, &options),
+            character_frequencies_with_n_threads_w_case(&text, 3, CaseSense::Insensitive)
+        );
+        let options = options.threads(1).filter(char::is_alphabetic);
+        let mut expected = character_frequencies_w_case(&text, CaseSense::Insensitive);
+        expected.retain(|character, _| character.is_alphabetic());
+        assert_eq!(count(&text, &options), expected);
+        assert_eq!(count("", &options), HashMap::new());
+    }
+
+    #[test]
+    fn test_count_options_sequential_merge() {
+        let text = "Hello, WORLD! ὈΔΥΣΣΕΎΣ\n".repeat(PARALLEL_THRESHOLD / 16);
+        for threads in [1, 2, 3, 8] {
+            for case in [CaseSense::Sensitive, CaseSense::Insensitive] {
+                let options = CountOptions::default().threads(threads).case(case);
+                let merged_on_join = count(&text, &options);
+                let merged_after = count(&text, &options.sequential_merge(true));
+                assert_eq!(merged_after, merged_on_join);
+                assert_eq!(merged_after, character_frequencies_w_case(&text, case));
+            }
         }
+        let options = CountOptions::default().threads(4).sequential_merge(true);
+        assert_eq!(count("", &options), HashMap::new());
     }
-    received.pop().unwrap()
-}
 
-pub fn sequential_character_frequencies(text: &str) -> HashMap<char, usize> {
-    character_frequencies_range(text, 0, text.len() - 1, CaseSense::InsensitiveASCIIOnly)
-}
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    fn test_count_options_normalization() {
+        let text = "cafe\u{301} café";
+        let options = CountOptions::default()
+            .case(CaseSense::Sensitive)
+            .normalization(Normalization::Nfc);
+        assert_eq!(
+            count(text, &options),
+            character_frequencies_normalized(text, CaseSense::Sensitive, Normalization::Nfc)
+        );
+    }
 
-// Same as sequential_character_frequencies but with Case Sensitivity
-/// # Example
-/// ```
-/// use character_frequency::*;
-/// # use std::collections::HashMap;
-/// let frequency_map = sequential_character_frequencies_w_case("Hello, WORLD",CaseSense::Sensitive);
-/// ```
-pub fn sequential_character_frequencies_w_case(
-    text: &str,
-    case: CaseSense,
-) -> HashMap<char, usize> {
-    character_frequencies_range(text, 0, text.len() - 1, case)
-}
+    #[test]
+    fn test_count_stats() {
+        let text = "abc".repeat(PARALLEL_THRESHOLD);
+        let (frequency_map, stats) = Counter::new().threads(4).count_with_stats(&text);
+        assert_eq!(frequency_map, sequential_character_frequencies(&text));
+        let expected = CountStats {
+            threads: 4,
+            chunks: 4,
+            chars: text.len(),
+        };
+        assert_eq!(stats, expected);
 
-fn character_frequencies_range(
-    text: &str,
-    from: usize,
-    to: usize,
-    case_sense: CaseSense,
-) -> HashMap<char, usize> {
-    let mut frequency_map: HashMap<char, usize> = HashMap::new();
-    for character in text.chars()
-        .skip(from)
-        .take(to - from + 1)
-        .map(|ch|  match case_sense {
-            CaseSense::Insensitive => match ch.to_lowercase().len() {
-                1 => ch.to_lowercase().next().unwrap(),
-       	        _ => panic!("Unicode character {:?} {} when converted to lowercase is a multicharacter String not a character", ch, ch ),},
-            CaseSense::InsensitiveASCIIOnly => ch.to_ascii_lowercase(),
-            CaseSense::Sensitive=> ch,})
+        let (_, stats) = Counter::new()
+            .threads(4)
+            .chunk_chars(1000)
+            .count_with_stats(&text);
+        assert_eq!(stats.chunks, text.len().div_ceil(1000));
+        assert_eq!(stats.threads, 4);
+
+        let (frequency_map, stats) = character_frequencies_with_stats("a", CaseSense::Sensitive);
+        assert_eq!(frequency_map, expected_freq("a1"));
+        let expected = CountStats {
+            threads: 1,
+            chunks: 1,
+            chars: 1,
+        };
+        assert_eq!(stats, expected);
+
+        let text = "a  \t b\n\nc".repeat(1000);
+        for counter in [Counter::new(), Counter::new().deterministic_order(true)] {
+            let counter = counter.threads(3).collapse_whitespace(true);
+            let (frequency_map, stats) = counter.count_with_stats(&text);
+            assert_eq!(frequency_map.values().sum::<usize>(), 5000);
+            assert_eq!(stats.chars, text.chars().count());
+        }
+        #[cfg(feature = "caseless")]
         {
-            *frequency_map.entry(character).or_insert(0) += 1;
+            let counter = Counter::new().threads(2).case(CaseSense::CaseFold);
+            let (frequency_map, stats) = counter.count_with_stats("aß");
+            assert_eq!(frequency_map, expected_freq("a1 s2"));
+            assert_eq!(stats.chars, 2);
         }
-    frequency_map
-}
-
-fn add_frequencies(a: HashMap<char, usize>, b: HashMap<char, usize>) -> HashMap<char, usize> {
-    let mut out = a;
-    for (character, frequency) in b {
-        *out.entry(character).or_insert(0) += frequency;
     }
-    out
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_many_distinct_chars() {
+        let text: String = (0x4E00..0x4E00 + 20000)
+            .chain(0x20000..0x20000 + 20000)
+            .filter_map(char::from_u32)
+            .collect::<String>()
+            .repeat(2);
+        let expected = sequential_character_frequencies_w_case(&text, CaseSense::Sensitive);
+        assert_eq!(expected.len(), 40000);
+        for threads in [2, 3, 8, 33] {
+            let result =
+                character_frequencies_with_n_threads_w_case(&text, threads, CaseSense::Sensitive);
+            assert_eq!(result, expected);
+        }
+        let result = Counter::new()
+            .threads(4)
+            .chunk_chars(1000)
+            .case(CaseSense::Sensitive)
+            .count(&text);
+        assert_eq!(result, expected);
+    }
 
-    // convenience function for testing; simplifies giving expected frequencies.
-    // given "a4 b3 c2 d1 e1", return hashmap {a:4, b:3, c:2, d;1, e:1}
-    fn expected_freq(s: &str) -> HashMap<char, usize> {
-        HashMap::<char, usize>::from_iter(s.split(" ").map(|chunk| {
-            (
-                chunk.chars().next().unwrap(),
-                usize::from_str_radix(&chunk.chars().skip(1).collect::<String>(), 10).unwrap(),
-            )
-        }))
+    #[test]
+    fn test_more_threads_than_chars() {
+        let text = "日本語ab";
+        let counter = Counter::new().threads(100).case(CaseSense::Sensitive);
+        let (frequency_map, stats) = counter.count_with_stats(text);
+        assert_eq!(frequency_map, expected_freq("日1 本1 語1 a1 b1"));
+        assert!(stats.chunks <= 5);
+        assert_eq!(stats.threads, stats.chunks);
+        assert_eq!(
+            character_frequencies_with_n_threads(text, 100),
+            frequency_map
+        );
+        let (_, stats) = Counter::new().threads(100).count_with_stats("");
+        assert_eq!(stats.chunks, 0);
     }
 
     #[test]
-    fn test_character_frequencies_range_full() {
-        let result =
-            character_frequencies_range("aaaabbbccd|@", 0, 11, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    fn test_add_frequencies_uneven() {
+        let large: HashMap<char, usize> = ('\u{4e00}'..'\u{5e00}').map(|ch| (ch, 2)).collect();
+        let small = expected_freq("a1 \u{4e00}3");
+        let mut expected = large.clone();
+        expected.insert('a', 1);
+        expected.insert('\u{4e00}', 5);
+        assert_eq!(add_frequencies(small.clone(), large.clone()), expected);
+        assert_eq!(add_frequencies(large.clone(), small.clone()), expected);
+        assert_eq!(add_frequencies(HashMap::new(), large.clone()), large);
+        assert_eq!(add_frequencies(large.clone(), HashMap::new()), large);
+
+        let small: BTreeMap<char, usize> = small.into_iter().collect();
+        let large: BTreeMap<char, usize> = large.into_iter().collect();
+        let expected: BTreeMap<char, usize> = expected.into_iter().collect();
+        assert_eq!(add_frequencies(small, large), expected);
     }
 
     #[test]
-    fn test_character_frequencies_range_consecutive_left() {
-        let result = character_frequencies_range("aaaa", 0, 2, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a3"));
+    fn test_subtract_frequencies() {
+        let total = expected_freq("a3 b2 c1");
+        let frequency_map = subtract_frequencies(total.clone(), &expected_freq("a1 b2"));
+        assert_eq!(frequency_map, expected_freq("a2 c1"));
+        let frequency_map = subtract_frequencies(total.clone(), &expected_freq("a5 c9"));
+        assert_eq!(frequency_map, expected_freq("b2"));
+        let frequency_map = subtract_frequencies(total.clone(), &expected_freq("x1 y2"));
+        assert_eq!(frequency_map, total);
+        let frequency_map = subtract_frequencies(expected_freq("a0 b1"), &HashMap::new());
+        assert_eq!(frequency_map, expected_freq("b1"));
     }
 
     #[test]
-    fn test_character_frequencies_range_consecutive_right() {
-        let result = character_frequencies_range("aaaa", 1, 3, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a3"));
+    fn test_both_cases() {
+        let text = "Hello, WORLD! ὈΔΥΣΣΕΎΣ ὀδυσσεύς";
+        for case in [
+            CaseSense::Insensitive,
+            CaseSense::InsensitiveASCIIOnly,
+            CaseSense::InsensitiveUppercase,
+        ] {
+            let (sensitive, insensitive) = character_frequencies_both_cases(text, case);
+            assert_eq!(
+                sensitive,
+                character_frequencies_w_case(text, CaseSense::Sensitive)
+            );
+            assert_eq!(insensitive, character_frequencies_w_case(text, case));
+        }
     }
 
     #[test]
-    fn test_character_frequencies_range_consecutive_center() {
-        let result = character_frequencies_range("aaaa", 1, 2, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a2"));
-        let result = character_frequencies_range("baab", 1, 2, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a2"));
-        let result = character_frequencies_range("bacb", 1, 2, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a1 c1"));
-        let result = character_frequencies_range("dcab", 1, 2, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a1 c1"));
+    fn test_collapse_whitespace() {
+        let text = "a   b\t\tc";
+        let mut expected = expected_freq("a1 b1 c1");
+        expected.insert(' ', 2);
+        for threads in [1, 2, 3, 4, 9] {
+            let counter = Counter::new().threads(threads).collapse_whitespace(true);
+            assert_eq!(counter.count(text), expected);
+            for chunk_chars in [1, 2, 4] {
+                assert_eq!(
+                    counter.clone().chunk_chars(chunk_chars).count(text),
+                    expected
+                );
+            }
+        }
+
+        let counter = Counter::new()
+            .threads(3)
+            .chunk_chars(1)
+            .collapse_whitespace(true);
+        assert_eq!(counter.count(" \n "), HashMap::from([(' ', 1)]));
+        assert_eq!(counter.count(""), HashMap::new());
+        assert_eq!(Counter::new().count(text)[&' '], 3);
     }
 
     #[test]
-    fn test_character_frequencies_range_consecutive_whole() {
-        let result = character_frequencies_range("aaaa", 0, 3, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a4"));
+    fn test_distinct_char_count() {
+        assert_eq!(distinct_char_count("aabbc", CaseSense::Sensitive), 3);
+        assert_eq!(
+            distinct_char_count("AaBb", CaseSense::InsensitiveASCIIOnly),
+            2
+        );
+        assert_eq!(distinct_char_count("AaBb", CaseSense::Sensitive), 4);
+        assert_eq!(distinct_char_count("", CaseSense::Sensitive), 0);
+        let text = "abc€日".repeat(PARALLEL_THRESHOLD);
+        assert_eq!(distinct_char_count(&text, CaseSense::Sensitive), 5);
     }
 
     #[test]
-    fn test_character_frequencies_range_only_one_left() {
-        let result = character_frequencies_range("aaa", 0, 0, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a1"));
+    #[cfg(feature = "regex")]
+    fn test_character_frequencies_matching() {
+        let greek = Regex::new(r"\p{Greek}").unwrap();
+        let text = "Hello ὈΔΥΣΣΕΎΣ world ὀδυσσεύς";
+        let frequency_map = character_frequencies_matching(text, &greek, CaseSense::Sensitive);
+        let mut expected = character_frequencies_w_case("ὈΔΥΣΣΕΎΣὀδυσσεύς", CaseSense::Sensitive);
+        assert_eq!(frequency_map, expected);
+        let frequency_map = character_frequencies_matching(text, &greek, CaseSense::Insensitive);
+        expected = character_frequencies_w_case("ὈΔΥΣΣΕΎΣὀδυσσεύς", CaseSense::Insensitive);
+        assert_eq!(frequency_map, expected);
+
+        let pairs = Regex::new("ll|o").unwrap();
+        let frequency_map = character_frequencies_matching(text, &pairs, CaseSense::Sensitive);
+        assert_eq!(frequency_map, expected_freq("o2"));
+
+        let long = text.repeat(PARALLEL_THRESHOLD / 8);
+        let frequency_map = character_frequencies_matching(&long, &greek, CaseSense::Sensitive);
+        assert_eq!(frequency_map[&'Σ'], 3 * (PARALLEL_THRESHOLD / 8));
+        assert_eq!(
+            character_frequencies_matching("", &greek, CaseSense::Sensitive),
+            HashMap::new()
+        );
     }
 
     #[test]
-    fn test_character_frequencies_range_only_one_right() {
-        let result = character_frequencies_range("aaa", 2, 2, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a1"));
+    fn test_character_positions() {
+        let text = "añaña, Año";
+        let positions = character_positions(text, CaseSense::Insensitive);
+        assert_eq!(positions[&'a'], (4, 0, 9));
+        assert_eq!(positions[&'ñ'], (3, 1, 10));
+        assert_eq!(positions[&'o'], (1, 12, 12));
+        assert_eq!(
+            character_positions("", CaseSense::Sensitive),
+            HashMap::new()
+        );
     }
 
     #[test]
-    fn test_character_frequencies_range_only_one_center() {
-        let result = character_frequencies_range("aaa", 1, 1, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(result, expected_freq("a1"));
+    fn test_mean_positions() {
+        let text = format!("{}zzz", "ab".repeat(100));
+        let positions = mean_positions(&text, CaseSense::Sensitive);
+        assert_eq!(positions[&'z'].0, 3);
+        assert!(positions[&'z'].1 > 0.99);
+        assert!((positions[&'a'].1 - 99.0 / 202.0).abs() < 1e-9);
+        assert_eq!(mean_positions("x", CaseSense::Sensitive)[&'x'], (1, 0.0));
+        assert_eq!(mean_positions("aA", CaseSense::Insensitive)[&'a'], (2, 0.5));
+        assert_eq!(mean_positions("", CaseSense::Sensitive), HashMap::new());
     }
 
     #[test]
-    fn test_sequential_character_frequencies() {
-        let result = character_frequencies("aaaabbbccd|@");
-        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    fn test_mean_positions_across_chunks() {
+        let text = format!("{}€€", "€ab\n".repeat(PARALLEL_THRESHOLD));
+        let positions = mean_positions(&text, CaseSense::Sensitive);
+        let chars: Vec<char> = text.chars().collect();
+        for (&character, &(frequency, mean)) in &positions {
+            let indices: Vec<usize> = (0..chars.len())
+                .filter(|&i| chars[i] == character)
+                .collect();
+            let expected = indices.iter().sum::<usize>() as f64
+                / indices.len() as f64
+                / (chars.len() - 1) as f64;
+            assert_eq!(frequency, indices.len());
+            assert!((mean - expected).abs() < 1e-9);
+        }
+        assert_eq!(positions.len(), 4);
     }
 
     #[test]
-    fn test_parallel_character_frequencies_more_threads_than_characters() {
-        let result = character_frequencies_with_n_threads("aaaabbbccd|@", 13);
-        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    fn test_ranked_frequencies_tiebreak() {
+        let text = "zzaa b";
+        let by_char = ranked_frequencies(text, CaseSense::Sensitive, Tiebreak::ByChar);
+        assert_eq!(by_char, vec![('a', 2), ('z', 2), (' ', 1), ('b', 1)]);
+        let by_first = ranked_frequencies(text, CaseSense::Sensitive, Tiebreak::ByFirstOccurrence);
+        assert_eq!(by_first, vec![('z', 2), ('a', 2), (' ', 1), ('b', 1)]);
+        assert_eq!(Tiebreak::default(), Tiebreak::ByChar);
+
+        let text = "zyx".repeat(PARALLEL_THRESHOLD);
+        let by_first = ranked_frequencies(&text, CaseSense::Sensitive, Tiebreak::ByFirstOccurrence);
+        let characters: String = by_first.iter().map(|&(character, _)| character).collect();
+        assert_eq!(characters, "zyx");
     }
 
     #[test]
-    fn test_parallel_character_frequencies_less_threads_than_characters() {
-        let result = character_frequencies_with_n_threads("aaaabbbccd|@", 5);
-        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    fn test_character_positions_across_chunks() {
+        let text = "€a€".repeat(PARALLEL_THRESHOLD);
+        let positions = character_positions(&text, CaseSense::Sensitive);
+        assert_eq!(positions[&'€'], (2 * PARALLEL_THRESHOLD, 0, text.len() - 3));
+        assert_eq!(positions[&'a'], (PARALLEL_THRESHOLD, 3, text.len() - 4));
     }
 
     #[test]
-    fn test_parallel_character_frequencies_single_thread() {
-        let result = character_frequencies_with_n_threads("aaaabbbccd|@", 1);
-        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    #[cfg(not(feature = "single-thread"))]
+    fn test_threads_from_env() {
+        let cpus = thread::available_parallelism().map(Into::into).unwrap_or(1);
+        assert_eq!(threads_from_env(Some("1")), 1);
+        assert_eq!(threads_from_env(Some("3")), 3);
+        assert_eq!(threads_from_env(Some(" 12\n")), 12);
+        assert_eq!(threads_from_env(Some("0")), 1);
+        assert_eq!(threads_from_env(Some("many")), cpus);
+        assert_eq!(threads_from_env(Some("")), cpus);
+        assert_eq!(threads_from_env(None), cpus);
     }
 
     #[test]
-    fn test_parallel_character_frequencies_prime_threads() {
-        let result = character_frequencies_with_n_threads("aaaabbbccd|@", 7);
-        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    fn test_iter_ranked() {
+        let frequency_map = FrequencyMap::from(expected_freq("a1 b4 c2 d2 e3"));
+        let top: Vec<(char, usize)> = frequency_map.iter_ranked().take(3).collect();
+        assert_eq!(top, vec![('b', 4), ('e', 3), ('c', 2)]);
+        assert_eq!(frequency_map.iter_ranked().count(), 5);
+        assert_eq!(FrequencyMap::default().iter_ranked().next(), None);
     }
 
     #[test]
-    fn test_parallel_character_frequencies_n_threads() {
-        let result = character_frequencies("aaaabbbccd|@");
-        assert_eq!(result, expected_freq("a4 b3 c2 d1 |1 @1"));
+    fn test_skip_unprintable() {
+        let text = "a\u{7}b\tc\u{200b}d\u{2028}e é";
+        let frequency_map =
+            character_frequencies_skipping(text, CaseSense::Sensitive, SkipSet::SKIP_UNPRINTABLE);
+        let mut expected = expected_freq("a1 b1 c1 d1 e1 é1");
+        expected.insert(' ', 1);
+        assert_eq!(frequency_map, expected);
     }
 
     #[test]
-    fn test_character_frequencies_range_full_w_case() {
-        let result = character_frequencies_range("AaaaBbBCCd|@", 0, 11, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a3 b1 C2 d1 |1 @1 A1 B2"));
+    fn test_escape_unprintable() {
+        assert_eq!(escape_unprintable('é'), "é");
+        assert_eq!(escape_unprintable(' '), " ");
+        assert_eq!(escape_unprintable('\n'), "\\n");
+        assert_eq!(escape_unprintable('\u{7}'), "\\u{7}");
+        assert_eq!(escape_unprintable('\u{a0}'), "\\u{a0}");
+        assert_eq!(escape_unprintable('\u{feff}'), "\\u{feff}");
     }
 
     #[test]
-    fn test_character_frequencies_range_consecutive_left_w_case() {
-        let result = character_frequencies_range("aaaA", 0, 2, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a3"));
-        let result = character_frequencies_range("Aaaa", 0, 2, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a2 A1"));
-        let result = character_frequencies_range("AaAa", 0, 2, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a1 A2"));
+    fn test_merge_all() {
+        let maps = vec![
+            expected_freq("a1 b2"),
+            expected_freq("b1 c3 d1"),
+            expected_freq("a4"),
+            HashMap::new(),
+            expected_freq("a1 b1 c1 d1 e1"),
+        ];
+        assert_eq!(merge_all(maps), expected_freq("a6 b4 c4 d2 e1"));
+        assert_eq!(merge_all(Vec::new()), HashMap::new());
+        assert_eq!(merge_all([expected_freq("z9")]), expected_freq("z9"));
     }
 
     #[test]
-    fn test_character_frequencies_range_consecutive_right_w_case() {
-        let result = character_frequencies_range("Aaaa", 1, 3, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a3"));
-        let result = character_frequencies_range("AaAa", 1, 3, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a2 A1"));
-        let result = character_frequencies_range("AaaA", 1, 3, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a2 A1"));
+    fn test_cumulative_distribution() {
+        let distribution = cumulative_distribution(&character_frequencies("aaab"));
+        assert_eq!(distribution, vec![('a', 3, 0.75), ('b', 1, 1.0)]);
+        let distribution = cumulative_distribution(&expected_freq("c2 a1 b1"));
+        assert_eq!(
+            distribution,
+            vec![('c', 2, 0.5), ('a', 1, 0.75), ('b', 1, 1.0)]
+        );
+        let distribution = cumulative_distribution(&character_frequencies("abcdefg"));
+        assert_eq!(distribution.last().unwrap().2, 1.0);
+        assert_eq!(cumulative_distribution(&HashMap::new()), vec![]);
+        assert_eq!(cumulative_distribution(&expected_freq("a0")), vec![]);
+        assert_eq!(cumulative_distribution(&expected_freq("a0 b0")), vec![]);
     }
 
     #[test]
-    fn test_character_frequencies_range_consecutive_center_w_case() {
-        let result = character_frequencies_range("aaaa", 1, 2, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a2"));
-        let result = character_frequencies_range("baAb", 1, 2, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a1 A1"));
-        let result = character_frequencies_range("bAcb", 1, 2, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("A1 c1"));
-        let result = character_frequencies_range("dcab", 1, 2, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a1 c1"));
+    fn test_percentages() {
+        let frequency_map = character_frequencies("aaab");
+        assert_eq!(
+            percentages(&frequency_map, 1),
+            vec![('a', 75.0), ('b', 25.0)]
+        );
+        let frequency_map = expected_freq("a1 b1 c1");
+        assert_eq!(
+            percentages(&frequency_map, 0),
+            vec![('a', 33.0), ('b', 33.0), ('c', 33.0)]
+        );
+        assert_eq!(
+            percentages(&expected_freq("x2 y1"), 3),
+            vec![('x', 66.667), ('y', 33.333)]
+        );
+        assert_eq!(percentages(&expected_freq("x1"), 400), vec![('x', 100.0)]);
+        assert_eq!(percentages(&expected_freq("x1"), 308), vec![('x', 100.0)]);
+        assert_eq!(
+            percentages(&expected_freq("x2 y1"), 20),
+            vec![('x', 200.0 / 3.0), ('y', 100.0 / 3.0)]
+        );
+        assert_eq!(percentages(&HashMap::new(), 2), vec![]);
+        assert_eq!(percentages(&expected_freq("a0"), 2), vec![]);
+        assert_eq!(
+            percentages(&expected_freq("a1 b0"), 2),
+            vec![('a', 100.0), ('b', 0.0)]
+        );
     }
 
     #[test]
-    fn test_character_frequencies_range_consecutive_whole_w_case() {
-        let result = character_frequencies_range("aaaa", 0, 3, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a4"));
-        let result = character_frequencies_range("aAaa", 0, 3, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("A1 a3"));
+    fn test_hapaxes() {
+        assert_eq!(hapaxes(&character_frequencies("aabc")), vec!['b', 'c']);
+        assert_eq!(hapaxes(&expected_freq("z1 a1 m2 b0")), vec!['a', 'z']);
+        assert_eq!(hapaxes(&expected_freq("a2 b3")), vec![]);
+        assert_eq!(hapaxes(&HashMap::new()), vec![]);
     }
 
     #[test]
-    fn test_character_frequencies_range_only_one_left_w_case() {
-        let result = character_frequencies_range("aaa", 0, 0, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a1"));
-        let result = character_frequencies_range("AaA", 0, 0, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("A1"));
+    fn test_cosine_similarity() {
+        let a = expected_freq("a3 b4");
+        assert!((cosine_similarity(&a, &a) - 1.0).abs() < 1e-12);
+        assert!((cosine_similarity(&a, &expected_freq("a6 b8")) - 1.0).abs() < 1e-12);
+        assert_eq!(cosine_similarity(&a, &expected_freq("c1 d9")), 0.0);
+        // (3 * 3) / (5 * 5)
+        let partial = cosine_similarity(&a, &expected_freq("a3 c4"));
+        assert!((partial - 0.36).abs() < 1e-12);
+        assert_eq!(partial, cosine_similarity(&expected_freq("a3 c4"), &a));
+        assert_eq!(cosine_similarity(&a, &HashMap::new()), 0.0);
+        assert_eq!(cosine_similarity(&HashMap::new(), &HashMap::new()), 0.0);
+        assert_eq!(
+            cosine_similarity(&expected_freq("a0"), &expected_freq("a0")),
+            0.0
+        );
     }
 
     #[test]
-    fn test_character_frequencies_range_only_one_right_w_case() {
-        let result = character_frequencies_range("aaa", 2, 2, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a1"));
-        let result = character_frequencies_range("BaA", 2, 2, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("A1"));
+    #[cfg(feature = "unicode-names2")]
+    fn test_annotated() {
+        let frequency_map = character_frequencies_w_case("\tΣΣ\t\ta", CaseSense::Sensitive);
+        let expected = vec![
+            ('\t', 3, "U+0009".to_string()),
+            ('Σ', 2, "GREEK CAPITAL LETTER SIGMA".to_string()),
+            ('a', 1, "LATIN SMALL LETTER A".to_string()),
+        ];
+        assert_eq!(annotated(&frequency_map), expected);
+        assert_eq!(annotated(&HashMap::new()), vec![]);
     }
 
     #[test]
-    fn test_character_frequencies_range_only_one_center_w_case() {
-        let result = character_frequencies_range("aaa", 1, 1, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a1"));
-        let result = character_frequencies_range("aAa", 1, 1, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("A1"));
+    fn test_to_json() {
+        let json = to_json(&expected_freq("a4 b1 c4"));
+        assert_eq!(
+            json,
+            r#"[{"char":"a","count":4},{"char":"c","count":4},{"char":"b","count":1}]"#
+        );
+        assert_eq!(to_json(&HashMap::new()), "[]");
+        assert_eq!(
+            to_json(&HashMap::from([('"', 1)])),
+            r#"[{"char":"\"","count":1}]"#
+        );
+        assert_eq!(
+            to_json(&HashMap::from([('\\', 1)])),
+            r#"[{"char":"\\","count":1}]"#
+        );
+        assert_eq!(
+            to_json(&HashMap::from([('\n', 2)])),
+            r#"[{"char":"\n","count":2}]"#
+        );
+        assert_eq!(
+            to_json(&HashMap::from([('\u{1}', 1)])),
+            r#"[{"char":"\u0001","count":1}]"#
+        );
+        assert_eq!(
+            to_json(&HashMap::from([('\u{9f}', 1)])),
+            r#"[{"char":"\u009f","count":1}]"#
+        );
+        assert_eq!(
+            to_json(&HashMap::from([('日', 1)])),
+            r#"[{"char":"日","count":1}]"#
+        );
     }
 
     #[test]
-    fn test_sequential_character_frequencies_w_case() {
-        let result = character_frequencies_w_case("AaabbbccdEEE|@", CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("A1 a2 b3 c2 d1 |1 @1 E3"));
+    fn test_retain_min() {
+        let frequency_map = retain_min(character_frequencies("aabc"), 2);
+        assert_eq!(frequency_map, expected_freq("a2"));
+        let frequency_map = retain_min(character_frequencies("aabc"), 0);
+        assert_eq!(frequency_map, expected_freq("a2 b1 c1"));
+        assert_eq!(retain_min(character_frequencies("aabc"), 3), HashMap::new());
     }
 
     #[test]
-    fn test_parallel_character_frequencies_more_threads_than_characters_w_case() {
-        let result =
-            character_frequencies_with_n_threads_w_case("AabbbccdE|@", 13, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("A1 a1 b3 c2 d1 |1 @1 E1"));
+    fn test_try_multicharacter_lowercase() {
+        let case = CaseSense::Insensitive;
+        let expected = Err(FreqError::MultiCharacterLowercase('İ'));
+        assert_eq!(
+            try_sequential_character_frequencies_w_case("aİ", case),
+            expected
+        );
+        assert_eq!(try_character_frequencies_w_case("abcdefİ", case), expected);
+        for threads in [1, 2, 3, 16] {
+            let result = try_character_frequencies_with_n_threads_w_case("abcİdef", threads, case);
+            assert_eq!(result, expected);
+        }
+        assert_eq!(try_character_frequencies_range("abİ", 1..3, case), expected);
+        assert_eq!(
+            try_character_frequencies_range("abİ", 0..2, case),
+            Ok(expected_freq("a1 b1"))
+        );
+        let result = try_character_frequencies_skipping("a İ", case, SkipSet::SKIP_WHITESPACE);
+        assert_eq!(result, expected);
+        let mut frequency_map = HashMap::new();
+        let result = try_count_into("aİ", case, &mut frequency_map);
+        assert_eq!(result, Err(FreqError::MultiCharacterLowercase('İ')));
+        assert_eq!(frequency_map, expected_freq("a1"));
     }
 
     #[test]
-    fn test_parallel_character_frequencies_less_threads_than_characters_w_case() {
-        let result =
-            character_frequencies_with_n_threads_w_case("AaaabbbccdEEE|@", 5, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("A1 a3 b3 c2 d1 |1 @1 E3"));
+    fn test_try_multicharacter_uppercase() {
+        let case = CaseSense::InsensitiveUppercase;
+        let expected = Err(FreqError::MultiCharacterUppercase('ß'));
+        assert_eq!(
+            try_sequential_character_frequencies_w_case("aß", case),
+            expected
+        );
+        assert_eq!(
+            try_character_frequencies_with_n_threads_w_case("aßb", 3, case),
+            expected
+        );
+        let result = try_character_frequencies_w_case("straße", CaseSense::Insensitive);
+        assert_eq!(result, Ok(expected_freq("s1 t1 r1 a1 ß1 e1")));
     }
 
     #[test]
-    fn test_parallel_character_frequencies_single_thread_w_case() {
-        let result =
-            character_frequencies_with_n_threads_w_case("aaaAbbBccd|@", 1, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a3 b2 B1 A1 c2 d1 |1 @1"));
+    fn test_expansion() {
+        let case = CaseSense::Insensitive;
+        let result = character_frequencies_w_expansion("aẞ", case, Expansion::Expand);
+        assert_eq!(result, expected_freq("a1 ß1"));
+        for expansion in [Expansion::Skip, Expansion::Replace('?')] {
+            let result = character_frequencies_w_expansion("aẞ", case, expansion);
+            assert_eq!(result, expected_freq("a1 ß1"));
+        }
+
+        let case = CaseSense::InsensitiveUppercase;
+        let result = character_frequencies_w_expansion("aẞß", case, Expansion::Expand);
+        assert_eq!(result, expected_freq("A1 ẞ1 S2"));
+        let result = character_frequencies_w_expansion("aẞß", case, Expansion::Skip);
+        assert_eq!(result, expected_freq("A1 ẞ1"));
+        let result = character_frequencies_w_expansion("aẞß", case, Expansion::Replace('?'));
+        assert_eq!(result, expected_freq("A1 ẞ1 ?1"));
+
+        let case = CaseSense::Insensitive;
+        let result = character_frequencies_w_expansion("İi", case, Expansion::Expand);
+        assert_eq!(result, expected_freq("i2 \u{307}1"));
+        let options = CountOptions::default()
+            .threads(3)
+            .case(case)
+            .expansion(Expansion::Replace('i'));
+        assert_eq!(count("İiİ", &options), expected_freq("i3"));
+        assert_eq!(Expansion::default(), Expansion::Expand);
+        let options = CountOptions::default().case(CaseSense::Insensitive);
+        assert_eq!(count("İ", &options), expected_freq("i1 \u{307}1"));
     }
 
     #[test]
-    fn test_parallel_character_frequencies_prime_threads_w_case() {
-        let result =
-            character_frequencies_with_n_threads_w_case("AaaBbbCcd|@", 7, CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a2 A1 B1 b2 c1 C1 d1 |1 @1"));
+    fn test_fold_char() {
+        // for each CaseSense, what 'A', 'a', 'Σ', 'σ' and '日' are counted as
+        let expected = [
+            (CaseSense::Insensitive, "aaσσ日"),
+            (CaseSense::InsensitiveASCIIOnly, "aaΣσ日"),
+            (CaseSense::Sensitive, "AaΣσ日"),
+            (CaseSense::InsensitiveLocale(Locale::Turkish), "aaσσ日"),
+            (CaseSense::InsensitiveUppercase, "AAΣΣ日"),
+            (CaseSense::InsensitiveASCIIOnlyUppercase, "AAΣσ日"),
+        ];
+        for (case, folded) in expected {
+            for (ch, expected) in "AaΣσ日".chars().zip(folded.chars()) {
+                assert_eq!(fold_char(ch, case), Some(expected));
+            }
+        }
+        let turkish = CaseSense::InsensitiveLocale(Locale::Turkish);
+        assert_eq!(fold_char('I', turkish), Some('ı'));
+        assert_eq!(fold_char('İ', turkish), Some('i'));
+        assert_eq!(fold_char('İ', CaseSense::Insensitive), None);
+        assert_eq!(fold_char('ß', CaseSense::InsensitiveUppercase), None);
+        assert_eq!(fold_char('ß', CaseSense::Insensitive), Some('ß'));
     }
 
     #[test]
-    fn test_parallel_character_frequencies_n_threads_w_case() {
-        let result = character_frequencies_w_case("AaaBbbCcd|@", CaseSense::Sensitive);
-        assert_eq!(result, expected_freq("a2 A1 B1 b2 c1 C1 d1 |1 @1"));
+    fn test_try_ok() {
+        let case = CaseSense::InsensitiveLocale(Locale::Turkish);
+        let result = try_character_frequencies_with_n_threads_w_case("İIiı", 4, case);
+        assert_eq!(result, Ok(expected_freq("i2 ı2")));
     }
 
     #[test]
-    fn test_unicode_case_sensitive() {
-        let greek_upper = "ὈΔΥΣΣΕΎΣ";
-        let greek_lower = "ὀδυσσεύς";
-        let greek_mix = "ὀδυσσεύςὈΔΥΣΣΕΎΣ";
-        let resultu = character_frequencies_w_case(greek_upper, CaseSense::Sensitive);
-        let resultl = character_frequencies_w_case(greek_lower, CaseSense::Sensitive);
-        let resultm = character_frequencies_w_case(greek_mix, CaseSense::Sensitive);
-        assert_eq!(resultu, expected_freq("Ὀ1 Δ1 Υ1 Σ3 Ε1 Ύ1"));
-        assert_eq!(resultl, expected_freq("ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1"));
-        assert_eq!(
-            resultm,
-            expected_freq("Ὀ1 Δ1 Υ1 Σ3 Ε1 Ύ1 ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1")
-        );
+    fn test_empty_text() {
+        assert_eq!(sequential_character_frequencies(""), HashMap::new());
+        assert_eq!(character_frequencies_with_n_threads("", 1), HashMap::new());
+        assert_eq!(character_frequencies_with_n_threads("", 4), HashMap::new());
+        let result = try_sequential_character_frequencies_w_case("", CaseSense::Insensitive);
+        assert_eq!(result, Ok(HashMap::new()));
     }
 
     #[test]
-    fn test_unicode_case_insensitiveasciionly() {
-        let greek_upper = "ὈΔΥΣΣΕΎΣ";
-        let greek_lower = "ὀδυσσεύς";
-        let greek_mix = "ὀδυσσεύςὈΔΥΣΣΕΎΣ";
-        let resultu = character_frequencies_w_case(greek_upper, CaseSense::InsensitiveASCIIOnly);
-        let resultl = character_frequencies_w_case(greek_lower, CaseSense::InsensitiveASCIIOnly);
-        let resultm = character_frequencies_w_case(greek_mix, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(resultu, expected_freq("Ὀ1 Δ1 Υ1 Σ3 Ε1 Ύ1"));
-        assert_eq!(resultl, expected_freq("ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1"));
+    fn test_case_sense_from_str_and_display() {
+        let cases = [
+            CaseSense::Insensitive,
+            CaseSense::InsensitiveASCIIOnly,
+            CaseSense::Sensitive,
+            CaseSense::InsensitiveLocale(Locale::Turkish),
+            CaseSense::InsensitiveLocale(Locale::Azerbaijani),
+            CaseSense::InsensitiveUppercase,
+            CaseSense::InsensitiveASCIIOnlyUppercase,
+        ];
+        for case in cases {
+            assert_eq!(case.to_string().parse(), Ok(case));
+            assert_eq!(case.to_string().to_uppercase().parse(), Ok(case));
+        }
+        assert_eq!("ASCII".parse(), Ok(CaseSense::InsensitiveASCIIOnly));
+        assert_eq!(CaseSense::Sensitive.to_string(), "sensitive");
+
+        let error = "bogus".parse::<CaseSense>().unwrap_err();
+        assert_eq!(error, ParseCaseSenseError("bogus".to_string()));
+        assert!(error
+            .to_string()
+            .starts_with("unknown case sense \"bogus\", expected sensitive, insensitive, "));
+        for name in CASE_SENSE_NAMES {
+            assert_eq!(name.parse::<CaseSense>().unwrap().to_string(), *name);
+        }
         assert_eq!(
-            resultm,
-            expected_freq("Ὀ1 Δ1 Υ1 Σ3 Ε1 Ύ1 ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1")
+            error.to_string().contains("casefold"),
+            cfg!(feature = "caseless")
         );
     }
 
     #[test]
-    fn test_unicode_case_insensitive() {
-        let greek_upper = "ὈΔΥΣΣΕΎΣ";
-        let greek_lower = "ὀδυσσεύς";
-        let greek_mix = "ὀδυσσεύςὈΔΥΣΣΕΎΣ";
-        let resultu = character_frequencies_w_case(greek_upper, CaseSense::Insensitive);
-        let resultl = character_frequencies_w_case(greek_lower, CaseSense::Insensitive);
-        let resultm = character_frequencies_w_case(greek_mix, CaseSense::Insensitive);
-        assert_eq!(resultu, expected_freq("ὀ1 δ1 υ1 σ3 ε1 ύ1"));
-        assert_eq!(resultl, expected_freq("ὀ1 δ1 υ1 σ2 ε1 ς1 ύ1"));
-        assert_eq!(resultm, expected_freq("ὀ2 δ2 υ2 σ5 ε2 ς1 ύ2"));
+    fn test_freq_error_display() {
+        let error = FreqError::MultiCharacterLowercase('İ');
+        assert_eq!(
+            error.to_string(),
+            "Unicode character 'İ' İ when converted to lowercase is a multicharacter String not a character"
+        );
     }
 
     #[test]
-    fn test_unicode_case_irrelevant() {
-        let chinese = "夫物芸芸，各復歸其根，歸根曰靜";
-        let expect = expected_freq("夫1 物1 芸2 ，2 各1 復1 其1 歸2 根2 曰1 靜1");
-        let resultc_s = character_frequencies_w_case(chinese, CaseSense::Sensitive);
-        let resultc_ia = character_frequencies_w_case(chinese, CaseSense::Insensitive);
-        let resultc_i = character_frequencies_w_case(chinese, CaseSense::InsensitiveASCIIOnly);
-        assert_eq!(resultc_s, expect);
-        assert_eq!(resultc_ia, expect);
-        assert_eq!(resultc_i, expect);
+    #[should_panic(expected = "MultiCharacterLowercase")]
+    fn test_infallible_panics_on_error() {
+        character_frequencies_with_n_threads_w_case("abİ", 2, CaseSense::Insensitive);
     }
 }
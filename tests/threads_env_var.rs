@@ -0,0 +1,23 @@
+// Changing the environment races with any other test reading it, so this is the only test
+// of its binary.
+#![cfg(all(feature = "std", not(feature = "single-thread")))]
+
+use character_frequency::*;
+
+#[test]
+fn test_threads_env_var() {
+    let text = "abc€".repeat(PARALLEL_THRESHOLD);
+    let expected = sequential_character_frequencies(&text);
+
+    std::env::set_var(THREADS_ENV_VAR, "1");
+    assert_eq!(character_frequencies(&text), expected);
+    assert_eq!(Counter::new().count_with_stats(&text).1.threads, 1);
+
+    std::env::set_var(THREADS_ENV_VAR, "3");
+    assert_eq!(character_frequencies(&text), expected);
+    assert_eq!(Counter::new().count_with_stats(&text).1.threads, 3);
+    // short texts are split between the threads too
+    let (_, stats) = character_frequencies_with_stats("abcdef", CaseSense::Sensitive);
+    assert_eq!((stats.threads, stats.chunks), (3, 3));
+    std::env::remove_var(THREADS_ENV_VAR);
+}